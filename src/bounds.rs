@@ -0,0 +1,115 @@
+use crate::prelude::*;
+
+/// An axis aligned bounding box.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy, Debug)]
+pub struct Aabb {
+    pub min: Vec3d,
+    pub max: Vec3d,
+}
+
+impl Default for Aabb {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+impl Aabb {
+    pub fn new(min: Vec3d, max: Vec3d) -> Self {
+        Self { min, max }
+    }
+
+    /// A box containing nothing, the identity of union.
+    pub fn empty() -> Self {
+        Self {
+            min: vec3d(f64::MAX, f64::MAX, f64::MAX),
+            max: vec3d(f64::MIN, f64::MIN, f64::MIN),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    /// Grows the box to contain p.
+    pub fn extend(&mut self, p: Vec3d) {
+        self.min = vec3d(
+            self.min.x.min(p.x),
+            self.min.y.min(p.y),
+            self.min.z.min(p.z),
+        );
+        self.max = vec3d(
+            self.max.x.max(p.x),
+            self.max.y.max(p.y),
+            self.max.z.max(p.z),
+        );
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        let mut b = *self;
+        b.extend(other.min);
+        b.extend(other.max);
+        b
+    }
+
+    /// Returns the box grown by the given amount on every side.
+    pub fn expand(&self, amount: f64) -> Aabb {
+        let a = vec3d(amount, amount, amount);
+        Aabb::new(self.min - a, self.max + a)
+    }
+
+    pub fn center(&self) -> Vec3d {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn size(&self) -> Vec3d {
+        self.max - self.min
+    }
+
+    /// The corner of the box selected per axis by the sign of a ray, 0 for min and 1 for max.
+    #[inline(always)]
+    fn corner(&self, sign: usize) -> Vec3d {
+        if sign == 0 {
+            self.min
+        } else {
+            self.max
+        }
+    }
+
+    /// Slab test of the ray against the box, returns the entry and exit distance clipped to
+    /// [0, t_max] if the ray crosses the box.
+    pub fn intersect(&self, ray: &Ray, t_max: f64) -> Option<(f64, f64)> {
+        let inv = ray.inv_direction;
+
+        // A ray parallel to a slab which starts on one of its planes gives 0 * inf, it counts
+        // as inside the slab.
+        let near = |t: f64| if t.is_nan() { f64::NEG_INFINITY } else { t };
+        let far = |t: f64| if t.is_nan() { f64::INFINITY } else { t };
+
+        let mut t_near = near((self.corner(ray.sign_x).x - ray.o.x) * inv.x);
+        let mut t_far = far((self.corner(1 - ray.sign_x).x - ray.o.x) * inv.x);
+
+        let ty_near = near((self.corner(ray.sign_y).y - ray.o.y) * inv.y);
+        let ty_far = far((self.corner(1 - ray.sign_y).y - ray.o.y) * inv.y);
+
+        if t_near > ty_far || ty_near > t_far {
+            return None;
+        }
+        t_near = t_near.max(ty_near);
+        t_far = t_far.min(ty_far);
+
+        let tz_near = near((self.corner(ray.sign_z).z - ray.o.z) * inv.z);
+        let tz_far = far((self.corner(1 - ray.sign_z).z - ray.o.z) * inv.z);
+
+        if t_near > tz_far || tz_near > t_far {
+            return None;
+        }
+        t_near = t_near.max(tz_near).max(0.0);
+        t_far = t_far.min(tz_far).min(t_max);
+
+        if t_near <= t_far {
+            Some((t_near, t_far))
+        } else {
+            None
+        }
+    }
+}
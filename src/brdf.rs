@@ -0,0 +1,84 @@
+use crate::prelude::*;
+use std::f64::consts::PI;
+
+/// The relative luminance of the linear color c.
+pub fn luminance(c: Vec3d) -> f64 {
+    0.2126 * c.x + 0.7152 * c.y + 0.0722 * c.z
+}
+
+/// Schlick's approximation of the Fresnel reflectance.
+pub fn fresnel_schlick(f0: Vec3d, cos_theta: f64) -> Vec3d {
+    let f = (1.0 - cos_theta).clamp(0.0, 1.0).powi(5);
+    f0 + (Vec3d::one() - f0) * f
+}
+
+/// The GGX normal distribution function.
+pub fn ggx_d(n_dot_h: f64, alpha: f64) -> f64 {
+    let a2 = alpha * alpha;
+    let d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    a2 / (PI * d * d)
+}
+
+/// The Smith masking function of GGX for one direction.
+pub fn smith_g1(n_dot_x: f64, alpha: f64) -> f64 {
+    let a2 = alpha * alpha;
+    2.0 * n_dot_x / (n_dot_x + (a2 + (1.0 - a2) * n_dot_x * n_dot_x).sqrt())
+}
+
+/// The base reflectance and the diffuse color of the material.
+pub fn specular_diffuse(material: &Material) -> (Vec3d, Vec3d) {
+    let f0 =
+        vec3d(0.04, 0.04, 0.04) * (1.0 - material.metallic) + material.color * material.metallic;
+    (f0, material.color * (1.0 - material.metallic))
+}
+
+/// The GGX alpha of the material, clamped to avoid singularities for mirrors.
+pub fn ggx_alpha(material: &Material) -> f64 {
+    (material.roughness * material.roughness).max(0.001)
+}
+
+/// Evaluates the GGX microfacet specular BRDF for the view direction v and light direction l.
+pub fn ggx_specular(n: Vec3d, v: Vec3d, l: Vec3d, f0: Vec3d, alpha: f64) -> Vec3d {
+    let n_dot_l = dot(n, l);
+    let n_dot_v = dot(n, v);
+    if n_dot_l <= 0.0 || n_dot_v <= 0.0 {
+        return Vec3d::zero();
+    }
+
+    let h = normalize(v + l);
+    let d = ggx_d(dot(n, h).max(0.0), alpha);
+    let g = smith_g1(n_dot_v, alpha) * smith_g1(n_dot_l, alpha);
+    let f = fresnel_schlick(f0, dot(v, h).max(0.0));
+
+    f * (d * g / (4.0 * n_dot_l * n_dot_v))
+}
+
+/// Samples a GGX distributed half vector around n.
+pub fn sample_ggx(n: Vec3d, alpha: f64, u: Vec2d) -> Vec3d {
+    let phi = 2.0 * PI * u.x;
+    let cos_theta = ((1.0 - u.y) / (1.0 + (alpha * alpha - 1.0) * u.y)).sqrt();
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+
+    to_world_frame(
+        vec3d(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta),
+        n,
+    )
+}
+
+/// Reflects the incident direction i at the normal n.
+pub fn reflect_dir(i: Vec3d, n: Vec3d) -> Vec3d {
+    i - n * (2.0 * dot(n, i))
+}
+
+/// Refracts the incident direction i at the normal n facing against it, eta is the ratio of the
+/// indices of refraction of the incident and the transmitted side. Returns None on total
+/// internal reflection.
+pub fn refract_dir(i: Vec3d, n: Vec3d, eta: f64) -> Option<Vec3d> {
+    let cos_i = -dot(n, i);
+    let k = 1.0 - eta * eta * (1.0 - cos_i * cos_i);
+    if k < 0.0 {
+        None
+    } else {
+        Some(normalize(i * eta + n * (eta * cos_i - k.sqrt())))
+    }
+}
@@ -0,0 +1,186 @@
+use crate::prelude::*;
+use std::cell::Cell;
+
+/// The maximum number of objects in a leaf.
+const LEAF_SIZE: usize = 2;
+
+thread_local! {
+    /// The traversal stack, reused by all rays of a thread.
+    static STACK: Cell<Vec<usize>> = const { Cell::new(Vec::new()) };
+}
+
+#[derive(PartialEq, Clone, Debug)]
+struct BvhNode {
+    bounds: Aabb,
+    /// For leaves the first entry in the index list, otherwise the index of the second child.
+    /// The first child always directly follows its parent.
+    offset: usize,
+    /// The number of objects of a leaf, 0 for inner nodes.
+    count: usize,
+}
+
+/// A bounding volume hierarchy over the bounds of the scene objects.
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct Bvh {
+    nodes: Vec<BvhNode>,
+    indices: Vec<usize>,
+    bounds: Vec<Aabb>,
+}
+
+impl Bvh {
+    /// Builds the hierarchy over the given bounds, the object index is the position in the slice.
+    pub fn new(bounds: &[Aabb]) -> Self {
+        let mut bvh = Bvh {
+            nodes: Vec::new(),
+            indices: (0..bounds.len()).collect(),
+            bounds: bounds.to_vec(),
+        };
+
+        if !bounds.is_empty() {
+            bvh.build(bounds, 0, bounds.len());
+        }
+
+        bvh
+    }
+
+    /// Recursively splits the objects in indices[start..end] at the median of the longest axis.
+    fn build(&mut self, bounds: &[Aabb], start: usize, end: usize) {
+        let node_bounds = self.indices[start..end]
+            .iter()
+            .fold(Aabb::empty(), |b, i| b.union(&bounds[*i]));
+
+        let node = self.nodes.len();
+        self.nodes.push(BvhNode {
+            bounds: node_bounds,
+            offset: start,
+            count: end - start,
+        });
+
+        if end - start <= LEAF_SIZE {
+            return;
+        }
+
+        let mut centers = Aabb::empty();
+        for i in &self.indices[start..end] {
+            centers.extend(bounds[*i].center());
+        }
+        let size = centers.size();
+        let axis = if size.x > size.y && size.x > size.z {
+            0
+        } else if size.y > size.z {
+            1
+        } else {
+            2
+        };
+
+        self.indices[start..end].sort_by(|a, b| {
+            component(bounds[*a].center(), axis)
+                .partial_cmp(&component(bounds[*b].center(), axis))
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+
+        let mid = (start + end) / 2;
+        self.build(bounds, start, mid);
+        let second = self.nodes.len();
+        self.build(bounds, mid, end);
+
+        self.nodes[node].offset = second;
+        self.nodes[node].count = 0;
+    }
+
+    /// Collects the sorted indices of all objects whose bounds the ray crosses within t_max and
+    /// returns the distance range along the ray covered by their bounds.
+    pub fn traverse(&self, ray: &Ray, t_max: f64, active: &mut Vec<usize>) -> Option<(f64, f64)> {
+        active.clear();
+        if self.nodes.is_empty() {
+            return None;
+        }
+
+        let mut range: Option<(f64, f64)> = None;
+        let mut stack = STACK.take();
+        stack.clear();
+        stack.push(0);
+
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if node.bounds.intersect(ray, t_max).is_some() {
+                if node.count > 0 {
+                    for object in &self.indices[node.offset..node.offset + node.count] {
+                        if let Some((t_near, t_far)) = self.bounds[*object].intersect(ray, t_max) {
+                            active.push(*object);
+                            range = Some(match range {
+                                Some((near, far)) => (near.min(t_near), far.max(t_far)),
+                                None => (t_near, t_far),
+                            });
+                        }
+                    }
+                } else {
+                    stack.push(node.offset);
+                    stack.push(index + 1);
+                }
+            }
+        }
+
+        STACK.set(stack);
+        active.sort_unstable();
+        range
+    }
+}
+
+fn component(v: Vec3d, axis: usize) -> f64 {
+    match axis {
+        0 => v.x,
+        1 => v.y,
+        _ => v.z,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn traversal_matches_brute_force() {
+        let mut rng = Pcg32::new(7);
+        let mut point = |scale: f64| {
+            vec3d(
+                (rng.next_f64() - 0.5) * scale,
+                (rng.next_f64() - 0.5) * scale,
+                (rng.next_f64() - 0.5) * scale,
+            )
+        };
+
+        let bounds: Vec<Aabb> = (0..37)
+            .map(|_| {
+                let mut b = Aabb::empty();
+                let corner = point(20.0);
+                b.extend(corner);
+                b.extend(corner + point(4.0));
+                b
+            })
+            .collect();
+        let bvh = Bvh::new(&bounds);
+
+        let mut active = Vec::new();
+        for _ in 0..500 {
+            let ray = Ray::new(point(30.0), normalize(point(2.0)));
+            let t_max = 25.0;
+
+            let mut expected = Vec::new();
+            let mut expected_range: Option<(f64, f64)> = None;
+            for (i, b) in bounds.iter().enumerate() {
+                if let Some((near, far)) = b.intersect(&ray, t_max) {
+                    expected.push(i);
+                    expected_range = Some(match expected_range {
+                        Some((n, f)) => (n.min(near), f.max(far)),
+                        None => (near, far),
+                    });
+                }
+            }
+
+            let range = bvh.traverse(&ray, t_max, &mut active);
+            assert_eq!(active, expected);
+            assert_eq!(range, expected_range);
+        }
+    }
+}
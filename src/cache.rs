@@ -0,0 +1,148 @@
+use crate::prelude::*;
+use rayon::prelude::*;
+
+/// The number of cells along each axis of a brick.
+const BRICK_SIZE: usize = 8;
+
+/// The number of cells along the longest axis of the object bounds.
+const RESOLUTION: usize = 64;
+
+/// A sparse brick grid of distance samples of a single object in world space. Bricks away from
+/// the surface store samples for trilinear lookup, bricks containing the surface are left empty
+/// and are evaluated exactly.
+#[derive(PartialEq, Clone, Debug)]
+pub struct DistanceCache {
+    origin: Vec3d,
+    cell: f64,
+    /// The number of bricks along each axis.
+    bricks: [usize; 3],
+    /// The (BRICK_SIZE + 1)^3 corner samples of each brick, None for surface bricks.
+    data: Vec<Option<Box<[f32]>>>,
+}
+
+impl DistanceCache {
+    /// Bakes the distance field of the object.
+    pub fn new(object: &Object) -> Self {
+        let bounds = object.bounds();
+        let size = bounds.size();
+        let cell = size.x.max(size.y).max(size.z) / RESOLUTION as f64;
+
+        // Keep a margin of a brick so that lookups just outside of the bounds hit the cache
+        let margin = cell * BRICK_SIZE as f64;
+        let origin = bounds.min - vec3d(margin, margin, margin);
+        let brick_extent = cell * BRICK_SIZE as f64;
+        let count = |extent: f64| ((extent + 2.0 * margin) / brick_extent).ceil().max(1.0) as usize;
+        let bricks = [count(size.x), count(size.y), count(size.z)];
+
+        let samples = BRICK_SIZE + 1;
+        let data = (0..bricks[0] * bricks[1] * bricks[2])
+            .into_par_iter()
+            .map(|index| {
+                let bx = index % bricks[0];
+                let by = (index / bricks[0]) % bricks[1];
+                let bz = index / (bricks[0] * bricks[1]);
+                let brick_origin = origin + vec3d(bx as f64, by as f64, bz as f64) * brick_extent;
+
+                let mut values = Vec::with_capacity(samples * samples * samples);
+                for z in 0..samples {
+                    for y in 0..samples {
+                        for x in 0..samples {
+                            let p = brick_origin + vec3d(x as f64, y as f64, z as f64) * cell;
+                            let d = object.distance(p);
+
+                            // The surface passes close to this brick, keep it exact
+                            if d.abs() < cell * 2.0 {
+                                return None;
+                            }
+                            values.push(d as f32);
+                        }
+                    }
+                }
+                Some(values.into_boxed_slice())
+            })
+            .collect();
+
+        Self {
+            origin,
+            cell,
+            bricks,
+            data,
+        }
+    }
+
+    /// Returns the cached distance at p, None if p lies outside of the grid or in a brick at the
+    /// surface, which need exact evaluation.
+    pub fn distance(&self, p: Vec3d) -> Option<f64> {
+        let q = (p - self.origin) / self.cell;
+        if q.x < 0.0 || q.y < 0.0 || q.z < 0.0 {
+            return None;
+        }
+
+        let (cx, cy, cz) = (q.x as usize, q.y as usize, q.z as usize);
+        let (bx, by, bz) = (cx / BRICK_SIZE, cy / BRICK_SIZE, cz / BRICK_SIZE);
+        if bx >= self.bricks[0] || by >= self.bricks[1] || bz >= self.bricks[2] {
+            return None;
+        }
+
+        let index = (bz * self.bricks[1] + by) * self.bricks[0] + bx;
+        let values = self.data[index].as_ref()?;
+
+        // Trilinear interpolation inside the cell
+        let (x, y, z) = (cx % BRICK_SIZE, cy % BRICK_SIZE, cz % BRICK_SIZE);
+        let (fx, fy, fz) = (q.x.fract(), q.y.fract(), q.z.fract());
+        let samples = BRICK_SIZE + 1;
+        let at = |dx: usize, dy: usize, dz: usize| {
+            values[((z + dz) * samples + y + dy) * samples + x + dx] as f64
+        };
+
+        let x00 = at(0, 0, 0) * (1.0 - fx) + at(1, 0, 0) * fx;
+        let x10 = at(0, 1, 0) * (1.0 - fx) + at(1, 1, 0) * fx;
+        let x01 = at(0, 0, 1) * (1.0 - fx) + at(1, 0, 1) * fx;
+        let x11 = at(0, 1, 1) * (1.0 - fx) + at(1, 1, 1) * fx;
+        let y0 = x00 * (1.0 - fy) + x10 * fy;
+        let y1 = x01 * (1.0 - fy) + x11 * fy;
+        let d = y0 * (1.0 - fz) + y1 * fz;
+
+        // Interpolation can overestimate the distance by up to half a cell diagonal, move the
+        // result towards the surface to stay conservative.
+        Some(d - d.signum() * self.cell * 0.87)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lookups_stay_close_and_conservative() {
+        let mut object = Object::new();
+        object.primitive = Primitive::Box {
+            size: vec3d(1.0, 0.6, 0.8),
+            rounding: 0.1,
+        };
+        object.transform.rotation = vec3d(20.0, 35.0, 0.0);
+        object.transform.scale = 1.5;
+        let cache = DistanceCache::new(&object);
+
+        let mut rng = Pcg32::new(11);
+        let mut hits = 0;
+        for _ in 0..5000 {
+            let p = vec3d(
+                (rng.next_f64() - 0.5) * 6.0,
+                (rng.next_f64() - 0.5) * 6.0,
+                (rng.next_f64() - 0.5) * 6.0,
+            );
+            let Some(d) = cache.distance(p) else {
+                continue;
+            };
+            hits += 1;
+
+            let exact = object.distance(p);
+            assert!((d - exact).abs() <= cache.cell * 2.0, "{} vs {}", d, exact);
+            // Marching on the cached distance must never step through the surface
+            assert!(d.abs() <= exact.abs() + 1e-4, "{} vs {}", d, exact);
+            assert_eq!(d.signum(), exact.signum());
+        }
+        assert!(hits > 1000);
+    }
+}
@@ -20,6 +20,12 @@ pub struct Camera {
     pub orbit_y: f64,
 }
 
+impl Default for Camera {
+    fn default() -> Self {
+        Self::new(vec3d(0.0, 1.0, 5.0), Vec3d::zero(), 70.0)
+    }
+}
+
 impl Camera {
     pub fn new(origin: Vec3d, center: Vec3d, fov: f64) -> Self {
         Self {
@@ -116,6 +122,20 @@ impl Camera {
         self.origin.z = self.center.z + radius * theta.sin() * phi.sin();
     }
 
+    /// Create a ray for the given camera mode
+    pub fn create_camera_ray(
+        &self,
+        mode: CameraMode,
+        uv: Vec2d,
+        screen: Vec2d,
+        offset: Vec2d,
+    ) -> Ray {
+        match mode {
+            CameraMode::Pinhole => self.create_ray(uv, screen, offset),
+            CameraMode::Orthogonal => self.create_ortho_ray(uv, screen, offset),
+        }
+    }
+
     /// Create a pinhole ray
     pub fn create_ray(&self, uv: Vec2d, screen: Vec2d, offset: Vec2d) -> Ray {
         let ratio = screen.x / screen.y;
@@ -138,7 +158,9 @@ impl Camera {
         dir += horizontal * (pixel_size.x * offset.x + uv.x);
         dir += vertical * (pixel_size.y * offset.y + uv.y);
 
-        Ray::new(self.origin, normalize(dir))
+        // The rays of neighboring pixels are about one pixel of the image plane apart, which
+        // lies at unit distance.
+        Ray::new(self.origin, normalize(dir)).with_footprint(0.0, half_width * 2.0 / screen.x)
     }
 
     pub fn create_ortho_ray(&self, uv: Vec2d, screen: Vec2d, offset: Vec2d) -> Ray {
@@ -164,7 +186,7 @@ impl Camera {
         out_origin += horizontal * (pixel_size.x * offset.x + uv.x - 0.5);
         out_origin += vertical * (pixel_size.y * offset.y + uv.y - 0.5);
 
-        Ray::new(out_origin, normalize(-w))
+        Ray::new(out_origin, normalize(-w)).with_footprint(half_width * 2.0 / screen.x, 0.0)
     }
 
     /// Computes the orbi camera vectors. Based on https://www.shadertoy.com/view/ttfyzN
@@ -214,6 +236,6 @@ impl Camera {
         let mut ray_dir = vec3d(screen.x, screen.y, camera_distance);
         ray_dir = normalize(Mat3d::from((camera_right, camera_up, camera_fwd)) * ray_dir);
 
-        Ray::new(camera_pos, ray_dir)
+        Ray::new(camera_pos, ray_dir).with_footprint(0.0, 2.0 / (screen_dim.x * camera_distance))
     }
 }
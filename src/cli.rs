@@ -0,0 +1,357 @@
+use crate::prelude::*;
+use rayon::prelude::*;
+use std::path::PathBuf;
+use std::time::Instant;
+
+const USAGE: &str = "Usage: signed render <project> [--pass <name|all>] [--size <width>x<height>] [--output <file.png|exr|pfm>]
+                     [--method <naive|over-relaxed>] [--antialiasing <supersampling|cone>]
+                     [--sampling <grid|stratified|\"blue noise\">] [--tone-mapping <none|reinhard|aces>]
+       signed bench <project> [--size <width>x<height>] [--precision <double|single>]
+       signed compare [<project>...] [--size <width>x<height>]";
+
+/// Runs the command line interface, returns false if no known command was given and the editor
+/// should be started. Exits with a non-zero status if the command fails.
+pub fn run() -> bool {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.is_empty() {
+        return false;
+    }
+
+    let result = match args[0].as_str() {
+        "render" => render(&args[1..]).map(|_| true),
+        "bench" => bench(&args[1..]).map(|_| true),
+        "compare" => compare(&args[1..]),
+        _ => return false,
+    };
+
+    match result {
+        Ok(true) => {}
+        Ok(false) => std::process::exit(1),
+        Err(err) => {
+            eprintln!("{}", err);
+            eprintln!("{}", USAGE);
+            std::process::exit(1);
+        }
+    }
+
+    true
+}
+
+/// Renders the passes of a project file into png, exr or pfm images.
+fn render(args: &[String]) -> Result<(), String> {
+    let mut project_path: Option<PathBuf> = None;
+    let mut passes = vec![RenderPass::Shaded];
+    let mut width = 800;
+    let mut height = 600;
+    let mut output = PathBuf::from("render.png");
+    let mut method = None;
+    let mut antialiasing = None;
+    let mut sampling = None;
+    let mut tone_mapping = None;
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--pass" => {
+                let name = iter.next().ok_or("Missing pass name")?;
+                passes = if name == "all" {
+                    RenderPass::all().to_vec()
+                } else {
+                    vec![RenderPass::from_name(name).ok_or(format!("Unknown pass {}", name))?]
+                };
+            }
+            "--size" => {
+                (width, height) = parse_size(iter.next().ok_or("Missing size")?)?;
+            }
+            "--output" => {
+                output = PathBuf::from(iter.next().ok_or("Missing output file")?);
+            }
+            "--method" => {
+                let name = iter.next().ok_or("Missing march method")?;
+                method = Some(
+                    MarchMethod::from_name(name).ok_or(format!("Unknown march method {}", name))?,
+                );
+            }
+            "--antialiasing" => {
+                let name = iter.next().ok_or("Missing anti-aliasing")?;
+                antialiasing = Some(
+                    AntiAliasing::from_name(name)
+                        .ok_or(format!("Unknown anti-aliasing {}", name))?,
+                );
+            }
+            "--sampling" => {
+                let name = iter.next().ok_or("Missing sample pattern")?;
+                sampling = Some(
+                    SamplePattern::from_name(name)
+                        .ok_or(format!("Unknown sample pattern {}", name))?,
+                );
+            }
+            "--tone-mapping" => {
+                let name = iter.next().ok_or("Missing tone mapping")?;
+                tone_mapping = Some(
+                    ToneMapping::from_name(name).ok_or(format!("Unknown tone mapping {}", name))?,
+                );
+            }
+            _ => project_arg(&mut project_path, arg)?,
+        }
+    }
+
+    let mut project = load_project(project_path)?;
+    project.settings.make_exact();
+    if let Some(method) = method {
+        project.settings.method = method;
+    }
+    if let Some(antialiasing) = antialiasing {
+        project.settings.antialiasing = antialiasing;
+    }
+    if let Some(sampling) = sampling {
+        project.settings.sampling = sampling;
+    }
+    if let Some(tone_mapping) = tone_mapping {
+        project.settings.tone_mapping = tone_mapping;
+    }
+
+    let mut tracer = Tracer::new();
+    if passes.len() == 1 {
+        if is_hdr_path(&output) {
+            let buffer = tracer.render_linear(&project, passes[0], width as usize, height as usize);
+            save_hdr(&buffer, &output).map_err(|err| err.to_string())?;
+        } else {
+            let mut buffer = TheRGBABuffer::new(TheDim::new(0, 0, width, height));
+            tracer.render_pass(&mut buffer, &project, passes[0]);
+            save_png(&buffer, &output).map_err(|err| err.to_string())?;
+        }
+    } else {
+        export_passes(&mut tracer, &project, &passes, width, height, &output)
+            .map_err(|err| err.to_string())?;
+    }
+
+    Ok(())
+}
+
+/// Marches the primary rays of a project with every march method and in packets, prints the
+/// step counts and compares scalar and packet distance evaluation.
+fn bench(args: &[String]) -> Result<(), String> {
+    let mut project_path: Option<PathBuf> = None;
+    let mut width = 800;
+    let mut height = 600;
+    let mut precision = None;
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--size" => {
+                (width, height) = parse_size(iter.next().ok_or("Missing size")?)?;
+            }
+            "--precision" => {
+                let name = iter.next().ok_or("Missing precision")?;
+                precision =
+                    Some(Precision::from_name(name).ok_or(format!("Unknown precision {}", name))?);
+            }
+            _ => project_arg(&mut project_path, arg)?,
+        }
+    }
+
+    let mut project = load_project(project_path)?;
+    if let Some(precision) = precision {
+        project.settings.precision = precision;
+    }
+    let mut tracer = Tracer::new();
+
+    let camera = project.camera.clone();
+    let (width, height) = (width as usize, height as usize);
+    let width_f = width as f64;
+    let height_f = height as f64;
+
+    let ray = |i: usize| {
+        let uv = vec2d((i % width) as f64 / width_f, (i / width) as f64 / height_f);
+        camera.create_camera_ray(
+            project.camera_mode,
+            uv,
+            vec2d(width_f, height_f),
+            vec2d(0.0, 0.0),
+        )
+    };
+
+    let mut baseline: Option<usize> = None;
+    let mut report = |name: &str, marches: &[March], time: std::time::Duration| {
+        let steps: usize = marches.iter().map(|m| m.iterations).sum();
+        let hits = marches.iter().filter(|m| m.hit()).count();
+        let step_limit = marches
+            .iter()
+            .filter(|m| m.termination == Termination::StepLimit)
+            .count();
+        let baseline = *baseline.get_or_insert(steps);
+
+        println!(
+            "{:<24} {:>12} {:>10.2} {:>8} {:>10} {:>10.2?} ({:+.1}% steps)",
+            name,
+            steps,
+            steps as f64 / marches.len() as f64,
+            hits,
+            step_limit,
+            time,
+            (steps as f64 / baseline as f64 - 1.0) * 100.0
+        );
+    };
+
+    println!(
+        "{:<24} {:>12} {:>10} {:>8} {:>10} {:>10}",
+        "method", "steps", "steps/ray", "hits", "step limit", "time"
+    );
+
+    for method in MarchMethod::all() {
+        for refine in [false, true] {
+            project.settings.method = method;
+            project.settings.refine = refine;
+            tracer.prepare(&project);
+
+            let t_max = project.settings.t_max;
+            let start = Instant::now();
+            let marches: Vec<March> = (0..width * height)
+                .into_par_iter()
+                .map(|i| tracer.march(&ray(i), t_max))
+                .collect();
+            let time = start.elapsed();
+
+            let name = if refine {
+                format!("{} + refine", method.name())
+            } else {
+                method.name().to_string()
+            };
+            report(&name, &marches, time);
+        }
+    }
+
+    // Neighboring rays marched together in packets
+    project.settings.method = MarchMethod::Naive;
+    project.settings.refine = false;
+    tracer.prepare(&project);
+
+    let t_max = project.settings.t_max;
+    let count = width * height;
+    let start = Instant::now();
+    let marches: Vec<March> = (0..count.div_ceil(LANES))
+        .into_par_iter()
+        .flat_map_iter(|packet| {
+            let first = packet * LANES;
+            let rays: [Ray; LANES] = std::array::from_fn(|k| ray((first + k).min(count - 1)));
+            tracer
+                .march_packet(&rays, t_max)
+                .into_iter()
+                .take(count - first)
+        })
+        .collect();
+    report("naive packets", &marches, start.elapsed());
+
+    // Distance evaluation of points along the primary rays on a single thread
+    let mut rng = Pcg32::new(7);
+    let points: Vec<Vec3d> = (0..1 << 18)
+        .map(|_| {
+            let i = (rng.next_f64() * count as f64) as usize;
+            ray(i.min(count - 1)).at(rng.next_f64() * t_max)
+        })
+        .collect();
+    let all: Vec<usize> = (0..project.objects.len()).collect();
+
+    let start = Instant::now();
+    let mut sum = 0.0;
+    for p in &points {
+        sum += tracer.map_active(*p, &all).distance;
+    }
+    std::hint::black_box(sum);
+    let scalar = start.elapsed().as_secs_f64();
+
+    let start = Instant::now();
+    let mut sum = 0.0;
+    for chunk in points.chunks_exact(LANES) {
+        let d = tracer.distance_packet(&Vec3Packet::from_fn(|k| chunk[k]), &all);
+        sum += d.0.iter().sum::<f64>();
+    }
+    std::hint::black_box(sum);
+    let packet = start.elapsed().as_secs_f64();
+
+    let per_point = 1e9 / points.len() as f64;
+    println!(
+        "distance evaluation: scalar {:.1} ns/point, packet {:.1} ns/point ({:.2}x)",
+        scalar * per_point,
+        packet * per_point,
+        scalar / packet
+    );
+
+    Ok(())
+}
+
+/// Checks that single and double precision agree within tolerance on the built-in sample scenes
+/// and the given projects, returns false if any scene differs.
+fn compare(args: &[String]) -> Result<bool, String> {
+    let mut scenes = sample_scenes();
+    let mut width = 160;
+    let mut height = 120;
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--size" => {
+                (width, height) = parse_size(iter.next().ok_or("Missing size")?)?;
+            }
+            _ if arg.starts_with("--") => return Err(format!("Unknown option {}", arg)),
+            _ => scenes.push((arg.clone(), load_project(Some(PathBuf::from(arg)))?)),
+        }
+    }
+
+    println!(
+        "{:<24} {:>16} {:>16} {:>8}",
+        "scene", "distance error", "ray mismatches", "result"
+    );
+
+    let mut agree = true;
+    for (name, project) in &scenes {
+        let (distance_error, ray_mismatches) =
+            compare_precision(project, width as usize, height as usize);
+        let ok = distance_error <= DISTANCE_TOLERANCE && ray_mismatches <= RAY_TOLERANCE;
+        agree &= ok;
+
+        println!(
+            "{:<24} {:>16.3e} {:>15.2}% {:>8}",
+            name,
+            distance_error,
+            ray_mismatches * 100.0,
+            if ok { "ok" } else { "FAILED" }
+        );
+    }
+
+    Ok(agree)
+}
+
+/// Parses a size given as <width>x<height>.
+fn parse_size(size: &str) -> Result<(i32, i32), String> {
+    let (w, h) = size
+        .split_once('x')
+        .ok_or("Size must be <width>x<height>")?;
+    let width = w.parse().map_err(|_| "Invalid width")?;
+    let height = h.parse().map_err(|_| "Invalid height")?;
+    if width <= 0 || height <= 0 {
+        return Err(str!("Width and height must be positive"));
+    }
+    Ok((width, height))
+}
+
+/// Takes a positional argument as the project path, unknown options and a second path are
+/// rejected.
+fn project_arg(project_path: &mut Option<PathBuf>, arg: &str) -> Result<(), String> {
+    if arg.starts_with("--") {
+        return Err(format!("Unknown option {}", arg));
+    }
+    if project_path.is_some() {
+        return Err(format!("Unexpected argument {}", arg));
+    }
+    *project_path = Some(PathBuf::from(arg));
+    Ok(())
+}
+
+fn load_project(path: Option<PathBuf>) -> Result<Project, String> {
+    let path = path.ok_or("Missing project file")?;
+    let contents = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+    serde_json::from_str(&contents).map_err(|err| err.to_string())
+}
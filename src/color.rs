@@ -0,0 +1,100 @@
+use crate::prelude::*;
+
+/// A linear RGBA float image. Rows run from the top of the screen down like in TheRGBABuffer.
+#[derive(PartialEq, Clone, Debug)]
+pub struct FloatBuffer {
+    width: usize,
+    height: usize,
+    pixels: Vec<f32>,
+}
+
+impl FloatBuffer {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![0.0; width * height * 4],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn pixels(&self) -> &[f32] {
+        &self.pixels
+    }
+
+    pub fn pixels_mut(&mut self) -> &mut [f32] {
+        &mut self.pixels
+    }
+
+    /// Copies the source buffer into this buffer with its top left corner at x, y.
+    pub fn copy_tile(&mut self, src: &FloatBuffer, x: usize, y: usize) {
+        copy_pixels(&src.pixels, src.width, &mut self.pixels, self.width, x, y);
+    }
+
+    /// Encodes the given pass into the 8-bit buffer of the same size, see [`encode_pass`].
+    pub fn encode(&self, buffer: &mut TheRGBABuffer, pass: RenderPass, settings: &RenderSettings) {
+        for (pixel, value) in buffer
+            .pixels_mut()
+            .chunks_exact_mut(4)
+            .zip(self.pixels.chunks_exact(4))
+        {
+            let c = vec3d(value[0] as f64, value[1] as f64, value[2] as f64);
+            let [r, g, b] = encode_pass(c, pass, settings);
+            pixel.copy_from_slice(&[r, g, b, to_u8(value[3] as f64)]);
+        }
+    }
+}
+
+/// Encodes a linear color of the pass into 8-bit values. The shaded pass is exposed, tone
+/// mapped and sRGB encoded, albedo colors are sRGB encoded and the data passes are written
+/// linearly.
+pub fn encode_pass(c: Vec3d, pass: RenderPass, settings: &RenderSettings) -> [u8; 3] {
+    let c = match pass {
+        RenderPass::Shaded => {
+            let c = tone_map(c * settings.exposure.exp2(), settings.tone_mapping);
+            vec3d(srgb_encode(c.x), srgb_encode(c.y), srgb_encode(c.z))
+        }
+        RenderPass::Albedo => vec3d(srgb_encode(c.x), srgb_encode(c.y), srgb_encode(c.z)),
+        _ => c,
+    };
+    [to_u8(c.x), to_u8(c.y), to_u8(c.z)]
+}
+
+/// Applies the tone mapping curve to the linear color.
+pub fn tone_map(c: Vec3d, tone_mapping: ToneMapping) -> Vec3d {
+    match tone_mapping {
+        ToneMapping::None => c,
+        ToneMapping::Reinhard => {
+            let f = |v: f64| v.max(0.0) / (1.0 + v.max(0.0));
+            vec3d(f(c.x), f(c.y), f(c.z))
+        }
+        ToneMapping::Aces => {
+            let f = |v: f64| {
+                let v = v.max(0.0);
+                (v * (2.51 * v + 0.03)) / (v * (2.43 * v + 0.59) + 0.14)
+            };
+            vec3d(f(c.x), f(c.y), f(c.z))
+        }
+    }
+}
+
+/// Encodes a linear value with the sRGB transfer function.
+pub fn srgb_encode(v: f64) -> f64 {
+    if v <= 0.0031308 {
+        v * 12.92
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Converts a value in [0, 1] to 8 bits, values outside of the range are clamped.
+fn to_u8(v: f64) -> u8 {
+    (v.clamp(0.0, 1.0) * 255.0).round() as u8
+}
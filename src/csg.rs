@@ -0,0 +1,162 @@
+use crate::prelude::*;
+
+/// How an object is combined with the objects before it.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy, Debug, Default)]
+pub enum CsgOp {
+    #[default]
+    Union,
+    Subtraction,
+    Intersection,
+}
+
+/// The result of a distance evaluation, tracks the nearest object and the object blended in by
+/// a smooth operation.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct SdfHit {
+    pub distance: f64,
+    pub id: usize,
+
+    pub blend_id: usize,
+    /// The weight of the blended object, 0.0 for no blending.
+    pub blend: f64,
+}
+
+impl SdfHit {
+    pub fn new(distance: f64, id: usize) -> Self {
+        Self {
+            distance,
+            id,
+
+            blend_id: id,
+            blend: 0.0,
+        }
+    }
+
+    /// An empty hit, used as the start value when combining objects.
+    pub fn miss() -> Self {
+        Self::new(f64::MAX, usize::MAX)
+    }
+
+    /// Returns true if the hit references an object.
+    pub fn is_valid(&self) -> bool {
+        self.id != usize::MAX
+    }
+}
+
+impl CsgOp {
+    /// Combines the accumulated hit a with the object hit b, k is the smoothing radius.
+    pub fn apply(&self, a: SdfHit, b: SdfHit, k: f64) -> SdfHit {
+        if !a.is_valid() {
+            return match self {
+                CsgOp::Union => b,
+                _ => a,
+            };
+        }
+
+        let (distance, h) = self.combine(a.distance, b.distance, k);
+        let h = h.value();
+        match self {
+            // The carved surface keeps the material of the remaining solid.
+            CsgOp::Subtraction => SdfHit { distance, ..a },
+            _ if k <= 0.0 => {
+                if h > 0.5 {
+                    a
+                } else {
+                    b
+                }
+            }
+            _ => blended(distance, a, b, h),
+        }
+    }
+
+    /// Combines the distances of a and b like [`CsgOp::apply`] without tracking the objects,
+    /// [`Real::empty`] stands for an empty scene.
+    #[inline(always)]
+    pub fn apply_distance<T: Real>(&self, a: T, b: T, k: f64) -> T {
+        if a.value() == T::empty().value() {
+            return match self {
+                CsgOp::Union => b,
+                _ => a,
+            };
+        }
+
+        self.combine(a, b, k).0
+    }
+
+    /// Returns the combined distance of a and b and the weight of a, which is 0.0 or 1.0 for
+    /// hard operations.
+    #[inline(always)]
+    fn combine<T: Real>(&self, a: T, b: T, k: f64) -> (T, T) {
+        let c = T::constant;
+        match self {
+            CsgOp::Union => {
+                if k <= 0.0 {
+                    (a.min(b), c(if a.value() < b.value() { 1.0 } else { 0.0 }))
+                } else {
+                    let h = (c(0.5) + c(0.5) * (b - a) / c(k)).clamp(0.0, 1.0);
+                    (mix(b, a, h) - c(k) * h * (c(1.0) - h), h)
+                }
+            }
+            CsgOp::Subtraction => {
+                if k <= 0.0 {
+                    (a.max(-b), c(1.0))
+                } else {
+                    let h = (c(0.5) - c(0.5) * (a + b) / c(k)).clamp(0.0, 1.0);
+                    (mix(a, -b, h) + c(k) * h * (c(1.0) - h), c(1.0) - h)
+                }
+            }
+            CsgOp::Intersection => {
+                if k <= 0.0 {
+                    (a.max(b), c(if a.value() > b.value() { 1.0 } else { 0.0 }))
+                } else {
+                    let h = (c(0.5) - c(0.5) * (b - a) / c(k)).clamp(0.0, 1.0);
+                    (mix(b, a, h) + c(k) * h * (c(1.0) - h), h)
+                }
+            }
+        }
+    }
+
+    /// Combines the distance ranges of a and b, None stands for an empty scene like
+    /// [`SdfHit::miss`]. Smooth operations move the surface by at most k / 4.
+    pub fn apply_interval(&self, a: Option<Interval>, b: Interval, k: f64) -> Option<Interval> {
+        let Some(a) = a else {
+            return match self {
+                CsgOp::Union => Some(b),
+                _ => None,
+            };
+        };
+
+        let blend = Interval::new(0.0, k.max(0.0) * 0.25);
+        Some(match self {
+            CsgOp::Union => a.min(b) - blend,
+            CsgOp::Subtraction => a.max(-b) + blend,
+            CsgOp::Intersection => a.max(b) + blend,
+        })
+    }
+}
+
+/// Creates the hit of a smooth operation, h is the weight of a. The dominant object becomes the
+/// id and the other one is blended in.
+fn blended(distance: f64, a: SdfHit, b: SdfHit, h: f64) -> SdfHit {
+    if h >= 0.5 {
+        SdfHit {
+            distance,
+            id: a.id,
+            blend_id: b.id,
+            blend: 1.0 - h,
+        }
+    } else {
+        SdfHit {
+            distance,
+            id: b.id,
+            blend_id: a.id,
+            blend: h,
+        }
+    }
+}
+
+/// Linear interpolation between x and y.
+#[inline(always)]
+fn mix<T: Scalar>(x: T, y: T, a: T) -> T {
+    x * (T::constant(1.0) - a) + y * a
+}
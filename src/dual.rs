@@ -0,0 +1,295 @@
+use crate::prelude::*;
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+/// A dual number for forward-mode automatic differentiation, carries a value and its gradient
+/// with respect to the evaluation point.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct Dual {
+    pub v: f64,
+    pub d: Vec3d,
+}
+
+impl Dual {
+    pub fn new(v: f64, d: Vec3d) -> Self {
+        Self { v, d }
+    }
+
+    /// A value which does not depend on the evaluation point.
+    pub fn constant(v: f64) -> Self {
+        Self::new(v, Vec3d::zero())
+    }
+
+    pub fn abs(self) -> Self {
+        if self.v < 0.0 {
+            -self
+        } else {
+            self
+        }
+    }
+
+    /// The gradient is zero at 0 where the derivative is undefined.
+    pub fn sqrt(self) -> Self {
+        let s = self.v.sqrt();
+        if s > 0.0 {
+            Self::new(s, self.d * (0.5 / s))
+        } else {
+            Self::constant(s)
+        }
+    }
+
+    pub fn exp(self) -> Self {
+        let e = self.v.exp();
+        Self::new(e, self.d * e)
+    }
+
+    pub fn sin(self) -> Self {
+        let (s, c) = self.v.sin_cos();
+        Self::new(s, self.d * c)
+    }
+
+    pub fn cos(self) -> Self {
+        let (s, c) = self.v.sin_cos();
+        Self::new(c, self.d * -s)
+    }
+
+    pub fn powi(self, n: i32) -> Self {
+        if n == 0 {
+            return Self::constant(1.0);
+        }
+        Self::new(self.v.powi(n), self.d * (n as f64 * self.v.powi(n - 1)))
+    }
+
+    /// The smaller value, carrying the gradient of the chosen branch.
+    pub fn min(self, other: Dual) -> Self {
+        if self.v <= other.v {
+            self
+        } else {
+            other
+        }
+    }
+
+    /// The larger value, carrying the gradient of the chosen branch.
+    pub fn max(self, other: Dual) -> Self {
+        if self.v >= other.v {
+            self
+        } else {
+            other
+        }
+    }
+
+    pub fn clamp(self, min: f64, max: f64) -> Self {
+        if self.v < min {
+            Self::constant(min)
+        } else if self.v > max {
+            Self::constant(max)
+        } else {
+            self
+        }
+    }
+}
+
+impl Add for Dual {
+    type Output = Dual;
+    fn add(self, other: Dual) -> Dual {
+        Dual::new(self.v + other.v, self.d + other.d)
+    }
+}
+
+impl Add<f64> for Dual {
+    type Output = Dual;
+    fn add(self, other: f64) -> Dual {
+        Dual::new(self.v + other, self.d)
+    }
+}
+
+impl Sub for Dual {
+    type Output = Dual;
+    fn sub(self, other: Dual) -> Dual {
+        Dual::new(self.v - other.v, self.d - other.d)
+    }
+}
+
+impl Sub<f64> for Dual {
+    type Output = Dual;
+    fn sub(self, other: f64) -> Dual {
+        Dual::new(self.v - other, self.d)
+    }
+}
+
+impl Mul for Dual {
+    type Output = Dual;
+    fn mul(self, other: Dual) -> Dual {
+        Dual::new(self.v * other.v, self.d * other.v + other.d * self.v)
+    }
+}
+
+impl Mul<f64> for Dual {
+    type Output = Dual;
+    fn mul(self, other: f64) -> Dual {
+        Dual::new(self.v * other, self.d * other)
+    }
+}
+
+impl Div for Dual {
+    type Output = Dual;
+    fn div(self, other: Dual) -> Dual {
+        let v = self.v / other.v;
+        Dual::new(v, (self.d - other.d * v) / other.v)
+    }
+}
+
+impl Div<f64> for Dual {
+    type Output = Dual;
+    fn div(self, other: f64) -> Dual {
+        Dual::new(self.v / other, self.d / other)
+    }
+}
+
+impl Neg for Dual {
+    type Output = Dual;
+    fn neg(self) -> Dual {
+        Dual::new(-self.v, Vec3d::zero() - self.d)
+    }
+}
+
+impl Scalar for Dual {
+    fn constant(v: f64) -> Self {
+        Dual::constant(v)
+    }
+
+    fn abs(self) -> Self {
+        Dual::abs(self)
+    }
+
+    fn sqrt(self) -> Self {
+        Dual::sqrt(self)
+    }
+
+    fn min(self, other: Self) -> Self {
+        Dual::min(self, other)
+    }
+
+    fn max(self, other: Self) -> Self {
+        Dual::max(self, other)
+    }
+
+    fn clamp(self, min: f64, max: f64) -> Self {
+        Dual::clamp(self, min, max)
+    }
+
+    fn powi(self, n: i32) -> Self {
+        Dual::powi(self, n)
+    }
+
+    fn sin(self) -> Self {
+        Dual::sin(self)
+    }
+
+    fn cos(self) -> Self {
+        Dual::cos(self)
+    }
+
+    fn exp(self) -> Self {
+        Dual::exp(self)
+    }
+}
+
+impl Real for Dual {
+    fn empty() -> Self {
+        Dual::constant(f64::MAX)
+    }
+
+    fn value(self) -> f64 {
+        self.v
+    }
+}
+
+/// A point of dual numbers.
+pub type Dual3 = Scalar3<Dual>;
+
+impl Dual3 {
+    /// The evaluation point p, seeded with the unit gradients of its coordinates.
+    pub fn variable(p: Vec3d) -> Self {
+        Self::new(
+            Dual::new(p.x, vec3d(1.0, 0.0, 0.0)),
+            Dual::new(p.y, vec3d(0.0, 1.0, 0.0)),
+            Dual::new(p.z, vec3d(0.0, 0.0, 1.0)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Compares the dual gradient of f with central differences at random points.
+    fn check_gradient(name: &str, f: impl Fn(&Dual3) -> Dual, distance: impl Fn(Vec3d) -> f64) {
+        let mut rng = Pcg32::new(13);
+        let h = 1e-6;
+        for _ in 0..500 {
+            let p = vec3d(
+                (rng.next_f64() - 0.5) * 4.0,
+                (rng.next_f64() - 0.5) * 4.0,
+                (rng.next_f64() - 0.5) * 4.0,
+            );
+
+            let dual = f(&Dual3::variable(p));
+            assert!((dual.v - distance(p)).abs() < 1e-12, "{} at {:?}", name, p);
+
+            let axes = [vec3d(h, 0.0, 0.0), vec3d(0.0, h, 0.0), vec3d(0.0, 0.0, h)];
+            let numeric = axes.map(|e| (distance(p + e) - distance(p - e)) / (2.0 * h));
+            let gradient = [dual.d.x, dual.d.y, dual.d.z];
+            assert!(
+                gradient
+                    .iter()
+                    .zip(&numeric)
+                    .all(|(g, n)| (g - n).abs() < 1e-4),
+                "{} at {:?}: {:?} vs {:?}",
+                name,
+                p,
+                gradient,
+                numeric
+            );
+        }
+    }
+
+    #[test]
+    fn object_gradients_match_central_differences() {
+        let primitives = [
+            Primitive::Sphere { radius: 1.0 },
+            Primitive::Box {
+                size: vec3d(0.8, 0.5, 1.2),
+                rounding: 0.1,
+            },
+            Primitive::Torus {
+                radius: 1.0,
+                thickness: 0.3,
+            },
+            Primitive::Capsule {
+                height: 1.5,
+                radius: 0.4,
+            },
+        ];
+
+        for primitive in primitives {
+            let mut object = Object::new();
+            object.primitive = primitive;
+            object.transform.position = vec3d(0.2, -0.3, 0.1);
+            object.transform.rotation = vec3d(25.0, -40.0, 10.0);
+            object.transform.scale = 1.3;
+
+            check_gradient(
+                &format!("{:?}", primitive),
+                |p| object.eval(p),
+                |p| object.distance(p),
+            );
+        }
+    }
+
+    #[test]
+    fn expr_gradients_match_central_differences() {
+        let text = "sin(x*20-1)*0.1 + y^2/(1+z^2) - sqrt(abs(x)+1)*exp(y/2) + cos(z)";
+        let expr = Expr::parse(text).unwrap();
+        check_gradient(text, |p| expr.eval(p), |p| expr.eval(&Scalar3::constant(p)));
+    }
+}
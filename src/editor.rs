@@ -1,8 +1,13 @@
 use crate::prelude::*;
 use lazy_static::lazy_static;
-use std::sync::mpsc::Receiver;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::time::Instant;
 use theframework::prelude::*;
 
+/// The resolution divisor of the preview right after a change, halved whenever a frame finished
+/// until the full resolution is reached.
+const PREVIEW_ZOOM: f32 = 8.0;
+
 lazy_static! {
     pub static ref RENDERVIEW: Mutex<RenderView> = Mutex::new(RenderView::default());
     pub static ref PANEL: Mutex<Panel> = Mutex::new(Panel::default());
@@ -16,7 +21,24 @@ pub struct EditorContext {
 
 pub struct Editor {
     project: Project,
-    update_tracker: UpdateTracker,
+
+    path_tracing: bool,
+
+    worker: RenderWorker,
+    /// The frame of the background render, at the resolution of the current job.
+    frame: TheRGBABuffer,
+    rendering: bool,
+    /// The finished and total steps of the current job, tiles or path traced samples.
+    progress: (usize, usize),
+    render_start: Instant,
+    preview_zoom: f32,
+    /// Set by project edits, camera moves and viewport resizes, the preview is only rendered
+    /// while it is stale.
+    dirty: bool,
+
+    /// Status texts of the exports running on their own threads.
+    export_sender: Sender<String>,
+    export_messages: Receiver<String>,
 
     context: EditorContext,
 
@@ -29,10 +51,24 @@ impl TheTrait for Editor {
     where
         Self: Sized,
     {
+        let (export_sender, export_messages) = channel();
+
         Self {
             sidebar: Sidebar::new(),
             event_receiver: None,
-            update_tracker: UpdateTracker::default(),
+
+            path_tracing: false,
+
+            worker: RenderWorker::new(),
+            frame: TheRGBABuffer::new(TheDim::new(0, 0, 0, 0)),
+            rendering: false,
+            progress: (0, 0),
+            render_start: Instant::now(),
+            preview_zoom: PREVIEW_ZOOM,
+            dirty: true,
+
+            export_sender,
+            export_messages,
 
             project: Project::default(),
             context: EditorContext {
@@ -114,32 +150,155 @@ impl TheTrait for Editor {
     fn update_ui(&mut self, ui: &mut TheUI, ctx: &mut TheContext) -> bool {
         let mut redraw = false;
 
-        let tick_update = self.update_tracker.update(500);
+        let pass = TRACER.lock().unwrap().pass;
+        let path_tracing = self.path_tracing && pass == RenderPass::Shaded;
+
+        // Take over the tiles finished by the background render
+        let generation = self.worker.generation();
+        let mut frame_changed = false;
+        let mut progress_changed = false;
+        while let Some(message) = self.worker.try_recv() {
+            match message {
+                RenderMessage::Started {
+                    generation: g,
+                    steps,
+                } if g == generation => {
+                    self.progress = (0, steps);
+                    self.render_start = Instant::now();
+                }
+                RenderMessage::Tile {
+                    generation: g,
+                    x,
+                    y,
+                    buffer,
+                } if g == generation => {
+                    copy_tile(&buffer, &mut self.frame, x, y);
+                    self.progress.0 += 1;
+                    frame_changed = true;
+                    progress_changed = true;
+                }
+                RenderMessage::Finished { generation: g } if g == generation => {
+                    self.rendering = false;
+                    progress_changed = true;
+                }
+                RenderMessage::Frame {
+                    generation: g,
+                    samples,
+                    buffer,
+                } if g == generation => {
+                    self.frame = buffer;
+                    self.progress.0 = samples as usize;
+                    frame_changed = true;
+                    progress_changed = true;
+                }
+                _ => {}
+            }
+        }
 
-        if tick_update {
-            if let Some(renderview) = ui.get_render_view("Render View") {
-                let dim = renderview.dim();
+        if progress_changed {
+            let elapsed = self.render_start.elapsed().as_secs_f64();
+            let (done, total) = self.progress;
+            let text = if path_tracing {
+                if !self.rendering {
+                    format!("Path traced {} samples in {:.2}s", done, elapsed)
+                } else {
+                    let eta = elapsed / done.max(1) as f64 * (total - done) as f64;
+                    format!(
+                        "Path tracing: {} of {} samples, {:.1}s remaining",
+                        done, total, eta
+                    )
+                }
+            } else if !self.rendering {
+                format!(
+                    "Rendered {}x{} in {:.2}s",
+                    self.frame.dim().width,
+                    self.frame.dim().height,
+                    elapsed
+                )
+            } else {
+                let eta = elapsed / done.max(1) as f64 * (total - done) as f64;
+                let resolution = if self.preview_zoom > 1.0 {
+                    format!(" at 1/{} resolution", self.preview_zoom as i32)
+                } else {
+                    String::new()
+                };
+                format!(
+                    "Rendering{}: {}% done, {:.1}s remaining",
+                    resolution,
+                    done * 100 / total.max(1),
+                    eta
+                )
+            };
+            ctx.ui.send(TheEvent::SetStatusText(TheId::empty(), text));
+        }
 
-                let zoom: f32 = 1.0;
+        while let Ok(text) = self.export_messages.try_recv() {
+            ctx.ui.send(TheEvent::SetStatusText(TheId::empty(), text));
+        }
 
-                let width = (dim.width as f32 / zoom) as i32;
-                let height = (dim.height as f32 / zoom) as i32;
+        if let Some(renderview) = ui.get_render_view("Render View") {
+            let dim = renderview.dim();
 
-                let buffer = renderview.render_buffer_mut();
-                buffer.resize(width, height);
-                TRACER.lock().unwrap().render(buffer, &self.project);
+            let buffer = renderview.render_buffer_mut();
+            let resized = buffer.dim().width != dim.width || buffer.dim().height != dim.height;
+            if resized {
+                buffer.resize(dim.width, dim.height);
+                self.dirty = true;
+            }
+
+            // A stale render restarts the preview at a coarse resolution which is refined
+            // every time a frame finished, the path tracer refines the full resolution frame
+            // with every sample
+            let zoom = if self.dirty {
+                Some(if path_tracing { 1.0 } else { PREVIEW_ZOOM })
+            } else if !path_tracing && !self.rendering && self.preview_zoom > 1.0 {
+                Some(self.preview_zoom / 2.0)
+            } else {
+                None
+            };
+
+            if let Some(zoom) = zoom {
+                let width = ((dim.width as f32 / zoom) as usize).max(1);
+                let height = ((dim.height as f32 / zoom) as usize).max(1);
+
+                // Start from the previous frame until the new tiles arrive
+                if self.frame.dim().width as usize != width
+                    || self.frame.dim().height as usize != height
+                {
+                    let mut frame =
+                        TheRGBABuffer::new(TheDim::new(0, 0, width as i32, height as i32));
+                    upscale_nearest(&self.frame, &mut frame);
+                    self.frame = frame;
+                }
+
+                self.worker.submit(RenderJob {
+                    project: self.project.clone(),
+                    pass,
+                    path_tracing,
+                    width,
+                    height,
+                });
+                self.preview_zoom = zoom;
+                self.rendering = true;
+                self.dirty = false;
+            }
+
+            if frame_changed || resized {
+                upscale_nearest(&self.frame, buffer);
                 redraw = true;
             }
+        }
 
-            if let Some(pointview) = ui.get_render_view("Point View") {
-                let dim = pointview.dim();
+        if let Some(pointview) = ui.get_render_view("Point View") {
+            let dim = pointview.dim();
 
-                let zoom: f32 = 1.0;
+            let zoom: f32 = 1.0;
 
-                let width = (dim.width as f32 / zoom) as i32;
-                let height = (dim.height as f32 / zoom) as i32;
+            let width = (dim.width as f32 / zoom) as i32;
+            let height = (dim.height as f32 / zoom) as i32;
 
-                let buffer = pointview.render_buffer_mut();
+            let buffer = pointview.render_buffer_mut();
+            if buffer.dim().width != width || buffer.dim().height != height {
                 buffer.resize(width, height);
                 //TRACER.lock().unwrap().render(buffer, &self.project);
                 redraw = true;
@@ -148,13 +307,23 @@ impl TheTrait for Editor {
 
         if let Some(receiver) = &mut self.event_receiver {
             while let Ok(event) = receiver.try_recv() {
-                redraw = self.sidebar.handle_event(
+                // Handled events edit the project or the render state, the render is stale
+                let mut edited = self.sidebar.handle_event(
                     &event,
                     ui,
                     ctx,
                     &mut self.project,
                     &mut self.context,
                 );
+                if RENDERVIEW.lock().unwrap().handle_event(
+                    &event,
+                    ui,
+                    ctx,
+                    &mut self.project,
+                    &mut self.context,
+                ) {
+                    edited = true;
+                }
                 if PANEL.lock().unwrap().handle_event(
                     &event,
                     ui,
@@ -162,7 +331,7 @@ impl TheTrait for Editor {
                     &mut self.project,
                     &mut self.context,
                 ) {
-                    redraw = true;
+                    edited = true;
                 }
                 match event {
                     TheEvent::FileRequesterResult(id, paths) => {
@@ -172,19 +341,58 @@ impl TheTrait for Editor {
                                 self.project =
                                     serde_json::from_str(&contents).unwrap_or(Project::default());
                                 self.sidebar.load_from_project(ui, ctx, &self.project);
-                                redraw = true;
+                                PANEL
+                                    .lock()
+                                    .unwrap()
+                                    .load_from_project(ui, ctx, &self.project);
+                                edited = true;
                             }
                         } else if id.name == "Save" {
                             for p in paths {
                                 let json = serde_json::to_string(&self.project).unwrap();
                                 std::fs::write(p, json).expect("Unable to write file");
                             }
+                        } else if id.name == "Export Passes" {
+                            if let Some(renderview) = ui.get_render_view("Render View") {
+                                let dim = renderview.dim();
+                                let (width, height) = (dim.width, dim.height);
+
+                                // Export on a thread of its own to keep the UI responsive
+                                let project = self.project.clone();
+                                let sender = self.export_sender.clone();
+                                std::thread::spawn(move || {
+                                    let mut tracer = Tracer::new();
+                                    for p in paths {
+                                        let text = match export_passes(
+                                            &mut tracer,
+                                            &project,
+                                            &RenderPass::all(),
+                                            width,
+                                            height,
+                                            &p,
+                                        ) {
+                                            Ok(()) => format!("Exported passes to {}", p.display()),
+                                            Err(err) => format!("Unable to export image: {}", err),
+                                        };
+                                        if sender.send(text).is_err() {
+                                            return;
+                                        }
+                                    }
+                                });
+                                ctx.ui.send(TheEvent::SetStatusText(
+                                    TheId::empty(),
+                                    str!("Exporting passes"),
+                                ));
+                            }
                         }
                     }
-                    TheEvent::StateChanged(id, _state) => {
+                    TheEvent::StateChanged(id, state) => {
                         // Open / Save Project
 
-                        if id.name == "Open" {
+                        if id.name == "Path Tracing" {
+                            self.path_tracing = state == TheWidgetState::Selected;
+                            edited = true;
+                        } else if id.name == "Open" {
                             ctx.ui.open_file_requester(
                                 TheId::named_with_id(id.name.as_str(), Uuid::new_v4()),
                                 "Open".into(),
@@ -224,6 +432,11 @@ impl TheTrait for Editor {
                     }
                     _ => {}
                 }
+
+                if edited {
+                    self.dirty = true;
+                    redraw = true;
+                }
             }
         }
         redraw
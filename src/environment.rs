@@ -0,0 +1,368 @@
+use crate::prelude::*;
+use std::f64::consts::PI;
+use std::path::PathBuf;
+
+/// The largest number of pixels accepted in a .hdr file.
+const MAX_HDR_PIXELS: usize = 1 << 25;
+
+/// An image based environment light, referencing an equirectangular Radiance .hdr file. The
+/// image itself is only decoded by the tracers.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct Environment {
+    pub name: String,
+    pub id: Uuid,
+
+    pub path: PathBuf,
+
+    /// Rotation around the y axis in degrees.
+    pub rotation: f64,
+    pub intensity: f64,
+}
+
+impl Environment {
+    pub fn new(name: String, path: PathBuf) -> Self {
+        Self {
+            name,
+            id: Uuid::new_v4(),
+
+            path,
+
+            rotation: 0.0,
+            intensity: 1.0,
+        }
+    }
+}
+
+/// The decoded environment used by the tracers. Diffuse irradiance is precomputed as 9 spherical
+/// harmonics coefficients.
+pub struct EnvironmentMap {
+    pub id: Uuid,
+
+    width: usize,
+    height: usize,
+    pixels: Vec<Vec3d>,
+    sh: [Vec3d; 9],
+
+    pub rotation: f64,
+    pub intensity: f64,
+}
+
+impl EnvironmentMap {
+    /// Loads and decodes the environment, returns None if the file is missing or not a valid .hdr
+    /// file.
+    pub fn new(environment: &Environment) -> Option<Self> {
+        let data = std::fs::read(&environment.path).ok()?;
+        let (width, height, pixels) = decode_hdr(&data)?;
+        if width == 0 || height == 0 {
+            return None;
+        }
+
+        let sh = project_sh(width, height, |x, y, _| pixels[y * width + x]);
+
+        Some(Self {
+            id: environment.id,
+
+            width,
+            height,
+            pixels,
+            sh,
+
+            rotation: environment.rotation,
+            intensity: environment.intensity,
+        })
+    }
+
+    /// The radiance arriving from the world space direction d.
+    pub fn sample(&self, d: Vec3d) -> Vec3d {
+        let (u, v) = direction_to_uv(self.to_local(d));
+
+        let x = u * self.width as f64 - 0.5;
+        let y = (v * self.height as f64 - 0.5).clamp(0.0, (self.height - 1) as f64);
+
+        let x0 = x.floor();
+        let y0 = y.floor();
+        let fx = x - x0;
+        let fy = y - y0;
+
+        let wrap = |x: f64| (x as i64).rem_euclid(self.width as i64) as usize;
+        let xa = wrap(x0);
+        let xb = wrap(x0 + 1.0);
+        let ya = y0 as usize;
+        let yb = (ya + 1).min(self.height - 1);
+
+        let top =
+            self.pixels[ya * self.width + xa] * (1.0 - fx) + self.pixels[ya * self.width + xb] * fx;
+        let bottom =
+            self.pixels[yb * self.width + xa] * (1.0 - fx) + self.pixels[yb * self.width + xb] * fx;
+
+        (top * (1.0 - fy) + bottom * fy) * self.intensity
+    }
+
+    /// The cosine weighted irradiance around the world space normal n, divided by PI so that
+    /// multiplying it with the albedo gives the outgoing diffuse radiance.
+    pub fn irradiance(&self, n: Vec3d) -> Vec3d {
+        sh_irradiance(&self.sh, self.to_local(n)) * self.intensity
+    }
+
+    /// Rotates the world space direction d into the space of the map.
+    fn to_local(&self, d: Vec3d) -> Vec3d {
+        rotate_y(d, -self.rotation.to_radians())
+    }
+}
+
+/// Maps a direction to equirectangular texture coordinates.
+pub fn direction_to_uv(d: Vec3d) -> (f64, f64) {
+    let u = 0.5 + d.x.atan2(-d.z) / (2.0 * PI);
+    let v = d.y.clamp(-1.0, 1.0).acos() / PI;
+    (u, v)
+}
+
+/// Maps equirectangular texture coordinates to a direction.
+pub fn uv_to_direction(u: f64, v: f64) -> Vec3d {
+    let phi = (u - 0.5) * 2.0 * PI;
+    let theta = v * PI;
+    vec3d(
+        theta.sin() * phi.sin(),
+        theta.cos(),
+        -theta.sin() * phi.cos(),
+    )
+}
+
+/// Projects the radiance of an equirectangular map onto the first three spherical harmonics
+/// bands. The radiance function receives the pixel coordinates and the direction of the pixel.
+pub fn project_sh<F>(width: usize, height: usize, radiance: F) -> [Vec3d; 9]
+where
+    F: Fn(usize, usize, Vec3d) -> Vec3d,
+{
+    let d_phi = 2.0 * PI / width as f64;
+    let d_theta = PI / height as f64;
+
+    let mut sh = [Vec3d::zero(); 9];
+    for y in 0..height {
+        let v = (y as f64 + 0.5) / height as f64;
+        let solid_angle = d_phi * d_theta * (v * PI).sin();
+
+        for x in 0..width {
+            let u = (x as f64 + 0.5) / width as f64;
+            let d = uv_to_direction(u, v);
+            let l = radiance(x, y, d) * solid_angle;
+
+            for (sh, basis) in sh.iter_mut().zip(sh_basis(d)) {
+                *sh += l * basis;
+            }
+        }
+    }
+    sh
+}
+
+/// Evaluates the cosine weighted irradiance around n from the spherical harmonics coefficients,
+/// divided by PI.
+pub fn sh_irradiance(sh: &[Vec3d; 9], n: Vec3d) -> Vec3d {
+    // Convolution with the clamped cosine lobe, Ramamoorthi and Hanrahan 2001
+    let bands = [
+        PI,
+        2.0 * PI / 3.0,
+        2.0 * PI / 3.0,
+        2.0 * PI / 3.0,
+        PI / 4.0,
+        PI / 4.0,
+        PI / 4.0,
+        PI / 4.0,
+        PI / 4.0,
+    ];
+
+    let mut e = Vec3d::zero();
+    for ((sh, band), basis) in sh.iter().zip(bands).zip(sh_basis(n)) {
+        e += *sh * (band * basis);
+    }
+
+    vec3d(e.x.max(0.0), e.y.max(0.0), e.z.max(0.0)) * (1.0 / PI)
+}
+
+/// The real spherical harmonics basis up to band 2.
+fn sh_basis(d: Vec3d) -> [f64; 9] {
+    [
+        0.282095,
+        0.488603 * d.y,
+        0.488603 * d.z,
+        0.488603 * d.x,
+        1.092548 * d.x * d.y,
+        1.092548 * d.y * d.z,
+        0.315392 * (3.0 * d.z * d.z - 1.0),
+        1.092548 * d.x * d.z,
+        0.546274 * (d.x * d.x - d.y * d.y),
+    ]
+}
+
+/// Decodes a Radiance RGBE .hdr image, returns the width, height and linear rgb pixels.
+pub fn decode_hdr(data: &[u8]) -> Option<(usize, usize, Vec<Vec3d>)> {
+    let mut pos = 0;
+
+    let magic = read_line(data, &mut pos)?;
+    if !magic.starts_with(b"#?") {
+        return None;
+    }
+
+    loop {
+        let line = read_line(data, &mut pos)?;
+        if line.is_empty() {
+            break;
+        }
+        if line.starts_with(b"FORMAT=") && line != b"FORMAT=32-bit_rle_rgbe" {
+            return None;
+        }
+    }
+
+    let resolution = std::str::from_utf8(read_line(data, &mut pos)?).ok()?;
+    let parts: Vec<&str> = resolution.split_whitespace().collect();
+    if parts.len() != 4 || parts[0] != "-Y" || parts[2] != "+X" {
+        return None;
+    }
+    let height: usize = parts[1].parse().ok()?;
+    let width: usize = parts[3].parse().ok()?;
+
+    // Reject sizes the remaining data cannot hold before allocating, a run length encoded
+    // scanline needs at least two bytes per channel for every 127 pixels.
+    let count = width.checked_mul(height)?;
+    let scanline_bytes = (4 * width).min(4 + 8 * width.div_ceil(127));
+    if count > MAX_HDR_PIXELS || height.checked_mul(scanline_bytes)? > data.len() - pos {
+        return None;
+    }
+
+    let mut pixels = Vec::with_capacity(count);
+    let mut scanline = vec![[0_u8; 4]; width];
+    for _ in 0..height {
+        read_scanline(data, &mut pos, &mut scanline)?;
+        pixels.extend(scanline.iter().map(rgbe_to_rgb));
+    }
+
+    Some((width, height, pixels))
+}
+
+/// Reads the next header line without the newline.
+fn read_line<'a>(data: &'a [u8], pos: &mut usize) -> Option<&'a [u8]> {
+    let start = *pos;
+    let end = start + data.get(start..)?.iter().position(|&b| b == b'\n')?;
+    *pos = end + 1;
+    Some(&data[start..end])
+}
+
+/// Reads a flat or run length encoded scanline.
+fn read_scanline(data: &[u8], pos: &mut usize, scanline: &mut [[u8; 4]]) -> Option<()> {
+    let width = scanline.len();
+    let header = data.get(*pos..*pos + 4)?;
+
+    if !(8..=0x7fff).contains(&width) || header[0] != 2 || header[1] != 2 || header[2] & 0x80 != 0 {
+        for pixel in scanline.iter_mut() {
+            pixel.copy_from_slice(data.get(*pos..*pos + 4)?);
+            *pos += 4;
+        }
+        return Some(());
+    }
+
+    if ((header[2] as usize) << 8 | header[3] as usize) != width {
+        return None;
+    }
+    *pos += 4;
+
+    for c in 0..4 {
+        let mut x = 0;
+        while x < width {
+            let count = *data.get(*pos)? as usize;
+            *pos += 1;
+
+            if count > 128 {
+                let count = count - 128;
+                let value = *data.get(*pos)?;
+                *pos += 1;
+                if x + count > width {
+                    return None;
+                }
+                for pixel in &mut scanline[x..x + count] {
+                    pixel[c] = value;
+                }
+                x += count;
+            } else {
+                if count == 0 || x + count > width {
+                    return None;
+                }
+                let values = data.get(*pos..*pos + count)?;
+                *pos += count;
+                for (pixel, value) in scanline[x..x + count].iter_mut().zip(values) {
+                    pixel[c] = *value;
+                }
+                x += count;
+            }
+        }
+    }
+
+    Some(())
+}
+
+fn rgbe_to_rgb(rgbe: &[u8; 4]) -> Vec3d {
+    if rgbe[3] == 0 {
+        return Vec3d::zero();
+    }
+    let f = 2.0_f64.powi(rgbe[3] as i32 - 136);
+    vec3d(rgbe[0] as f64 * f, rgbe[1] as f64 * f, rgbe[2] as f64 * f)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(width: usize, height: usize) -> Vec<u8> {
+        format!(
+            "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n",
+            height, width
+        )
+        .into_bytes()
+    }
+
+    /// A flat image, scanlines narrower than 8 pixels are never run length encoded.
+    fn flat_hdr() -> Vec<u8> {
+        let mut data = header(4, 2);
+        for i in 0..8 {
+            data.extend_from_slice(&[i * 10, 64, 128, 129]);
+        }
+        data
+    }
+
+    /// A run length encoded image, every channel of a scanline is a single run.
+    fn rle_hdr() -> Vec<u8> {
+        let mut data = header(8, 3);
+        for _ in 0..3 {
+            data.extend_from_slice(&[2, 2, 0, 8]);
+            for value in [32, 64, 128, 129] {
+                data.extend_from_slice(&[128 + 8, value]);
+            }
+        }
+        data
+    }
+
+    #[test]
+    fn decodes_complete_images() {
+        let (width, height, pixels) = decode_hdr(&flat_hdr()).unwrap();
+        assert_eq!((width, height, pixels.len()), (4, 2, 8));
+
+        let (width, height, pixels) = decode_hdr(&rle_hdr()).unwrap();
+        assert_eq!((width, height, pixels.len()), (8, 3, 24));
+        assert!(pixels.iter().all(|p| *p == pixels[0]));
+    }
+
+    #[test]
+    fn rejects_truncated_images() {
+        for data in [flat_hdr(), rle_hdr()] {
+            for len in 0..data.len() {
+                assert!(decode_hdr(&data[..len]).is_none(), "{} bytes", len);
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_sizes_larger_than_the_data() {
+        let mut data = header(30000, 30000);
+        data.extend_from_slice(&[2, 2, 0x75, 0x30]);
+        assert!(decode_hdr(&data).is_none());
+    }
+}
@@ -0,0 +1,268 @@
+use crate::prelude::*;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// Saves the buffer as an 8-bit RGBA png image.
+pub fn save_png(buffer: &TheRGBABuffer, path: &Path) -> Result<(), png::EncodingError> {
+    let file = File::create(path)?;
+    let writer = BufWriter::new(file);
+
+    let mut encoder = png::Encoder::new(
+        writer,
+        buffer.dim().width as u32,
+        buffer.dim().height as u32,
+    );
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(buffer.pixels())
+}
+
+/// Returns true if the path has the extension of a float image format, exr or pfm.
+pub fn is_hdr_path(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|e| e.to_str()),
+        Some("exr") | Some("pfm")
+    )
+}
+
+/// Saves the linear buffer as an exr image if the path ends in .exr and as pfm otherwise.
+pub fn save_hdr(buffer: &FloatBuffer, path: &Path) -> std::io::Result<()> {
+    if path.extension().and_then(|e| e.to_str()) == Some("exr") {
+        save_exr(buffer, path)
+    } else {
+        save_pfm(buffer, path)
+    }
+}
+
+/// Saves the RGB channels of the buffer as a little endian pfm image, rows run bottom up.
+pub fn save_pfm(buffer: &FloatBuffer, path: &Path) -> std::io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    write!(writer, "PF\n{} {}\n-1.0\n", buffer.width(), buffer.height())?;
+
+    for row in buffer.pixels().rchunks_exact(buffer.width() * 4) {
+        for pixel in row.chunks_exact(4) {
+            for value in &pixel[..3] {
+                writer.write_all(&value.to_le_bytes())?;
+            }
+        }
+    }
+    writer.flush()
+}
+
+/// Saves the buffer as an uncompressed scanline OpenEXR image with 32-bit float RGBA channels.
+pub fn save_exr(buffer: &FloatBuffer, path: &Path) -> std::io::Result<()> {
+    let (width, height) = (buffer.width(), buffer.height());
+
+    let mut header = Vec::new();
+    let mut attribute = |name: &str, kind: &str, value: &[u8]| {
+        header.extend_from_slice(name.as_bytes());
+        header.push(0);
+        header.extend_from_slice(kind.as_bytes());
+        header.push(0);
+        header.extend_from_slice(&(value.len() as i32).to_le_bytes());
+        header.extend_from_slice(value);
+    };
+
+    // Channels are stored in alphabetical order
+    let channels = [("A", 3), ("B", 2), ("G", 1), ("R", 0)];
+    let mut list = Vec::new();
+    for (name, _) in &channels {
+        list.extend_from_slice(name.as_bytes());
+        list.push(0);
+        list.extend_from_slice(&2i32.to_le_bytes()); // FLOAT
+        list.extend_from_slice(&[0, 0, 0, 0]); // pLinear and reserved
+        list.extend_from_slice(&1i32.to_le_bytes()); // x sampling
+        list.extend_from_slice(&1i32.to_le_bytes()); // y sampling
+    }
+    list.push(0);
+
+    let window: Vec<u8> = [0, 0, width as i32 - 1, height as i32 - 1]
+        .iter()
+        .flat_map(|v| v.to_le_bytes())
+        .collect();
+
+    attribute("channels", "chlist", &list);
+    attribute("compression", "compression", &[0]);
+    attribute("dataWindow", "box2i", &window);
+    attribute("displayWindow", "box2i", &window);
+    attribute("lineOrder", "lineOrder", &[0]);
+    attribute("pixelAspectRatio", "float", &1.0f32.to_le_bytes());
+    attribute("screenWindowCenter", "v2f", &[0; 8]);
+    attribute("screenWindowWidth", "float", &1.0f32.to_le_bytes());
+    header.push(0);
+
+    let mut writer = BufWriter::new(File::create(path)?);
+    writer.write_all(&[0x76, 0x2f, 0x31, 0x01, 2, 0, 0, 0])?;
+    writer.write_all(&header)?;
+
+    // The offset table points to every scanline
+    let line_size = 8 + width * 4 * channels.len();
+    let first_line = 8 + header.len() + height * 8;
+    for y in 0..height {
+        writer.write_all(&((first_line + y * line_size) as u64).to_le_bytes())?;
+    }
+
+    for (y, row) in buffer.pixels().chunks_exact(width * 4).enumerate() {
+        writer.write_all(&(y as i32).to_le_bytes())?;
+        writer.write_all(&((line_size - 8) as i32).to_le_bytes())?;
+        for (_, channel) in &channels {
+            for pixel in row.chunks_exact(4) {
+                writer.write_all(&pixel[*channel].to_le_bytes())?;
+            }
+        }
+    }
+    writer.flush()
+}
+
+/// Renders the passes of the project at the given size and saves each one as its own image.
+/// The pass name is appended to the file name, i.e. render.png becomes render_depth.png. Paths
+/// ending in .exr or .pfm store the linear colors. Exports are always rendered exactly, see
+/// [`RenderSettings::make_exact`].
+pub fn export_passes(
+    tracer: &mut Tracer,
+    project: &Project,
+    passes: &[RenderPass],
+    width: i32,
+    height: i32,
+    path: &Path,
+) -> Result<(), png::EncodingError> {
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "render".to_string());
+
+    let mut project = project.clone();
+    project.settings.make_exact();
+
+    if is_hdr_path(path) {
+        let extension = path.extension().unwrap_or_default().to_string_lossy();
+        for pass in passes {
+            let buffer = tracer.render_linear(&project, *pass, width as usize, height as usize);
+            save_hdr(
+                &buffer,
+                &path.with_file_name(format!("{}_{}.{}", stem, pass.name(), extension)),
+            )?;
+        }
+        return Ok(());
+    }
+
+    let mut buffer = TheRGBABuffer::new(TheDim::new(0, 0, width, height));
+    for pass in passes {
+        tracer.render_pass(&mut buffer, &project, *pass);
+        save_png(
+            &buffer,
+            &path.with_file_name(format!("{}_{}.png", stem, pass.name())),
+        )?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A buffer where every channel holds its index in the pixel data.
+    fn test_buffer(width: usize, height: usize) -> FloatBuffer {
+        let mut buffer = FloatBuffer::new(width, height);
+        for (i, value) in buffer.pixels_mut().iter_mut().enumerate() {
+            *value = i as f32;
+        }
+        buffer
+    }
+
+    fn read_f32(data: &[u8], offset: usize) -> f32 {
+        f32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+    }
+
+    fn write_temp(name: &str, save: impl Fn(&Path) -> std::io::Result<()>) -> Vec<u8> {
+        let path = std::env::temp_dir().join(format!("{}_{}", std::process::id(), name));
+        save(&path).unwrap();
+        let data = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        data
+    }
+
+    #[test]
+    fn pfm_layout() {
+        let (width, height) = (3, 2);
+        let buffer = test_buffer(width, height);
+        let data = write_temp("layout.pfm", |path| save_pfm(&buffer, path));
+
+        let header = b"PF\n3 2\n-1.0\n";
+        assert_eq!(&data[..header.len()], header);
+        assert_eq!(data.len(), header.len() + width * height * 3 * 4);
+
+        // Rows are stored bottom up without alpha
+        let mut offset = header.len();
+        for y in (0..height).rev() {
+            for x in 0..width {
+                for c in 0..3 {
+                    let expected = ((y * width + x) * 4 + c) as f32;
+                    assert_eq!(read_f32(&data, offset), expected);
+                    offset += 4;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn exr_layout() {
+        let (width, height) = (3, 2);
+        let buffer = test_buffer(width, height);
+        let data = write_temp("layout.exr", |path| save_exr(&buffer, path));
+
+        assert_eq!(&data[..8], &[0x76, 0x2f, 0x31, 0x01, 2, 0, 0, 0]);
+
+        // Walk the attributes up to the null byte ending the header
+        let mut offset = 8;
+        let mut names = Vec::new();
+        while data[offset] != 0 {
+            let mut read_string = || {
+                let end = offset + data[offset..].iter().position(|b| *b == 0).unwrap();
+                let text = String::from_utf8(data[offset..end].to_vec()).unwrap();
+                offset = end + 1;
+                text
+            };
+            let name = read_string();
+            let _kind = read_string();
+            let size = i32::from_le_bytes(data[offset..offset + 4].try_into().unwrap());
+            offset += 4 + size as usize;
+            names.push(name);
+        }
+        offset += 1;
+        for name in [
+            "channels",
+            "compression",
+            "dataWindow",
+            "displayWindow",
+            "lineOrder",
+        ] {
+            assert!(names.iter().any(|n| n == name), "missing {}", name);
+        }
+
+        // The offset table points to the scanlines, which store the channels A, B, G, R
+        let line_size = 8 + width * 4 * 4;
+        assert_eq!(data.len(), offset + height * 8 + height * line_size);
+        for y in 0..height {
+            let entry = offset + y * 8;
+            let line = u64::from_le_bytes(data[entry..entry + 8].try_into().unwrap()) as usize;
+            assert_eq!(line, offset + height * 8 + y * line_size);
+
+            assert_eq!(&data[line..line + 4], &(y as i32).to_le_bytes());
+            assert_eq!(
+                &data[line + 4..line + 8],
+                &((line_size - 8) as i32).to_le_bytes()
+            );
+            for (i, channel) in [3, 2, 1, 0].iter().enumerate() {
+                for x in 0..width {
+                    let expected = ((y * width + x) * 4 + channel) as f32;
+                    assert_eq!(read_f32(&data, line + 8 + (i * width + x) * 4), expected);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,196 @@
+use crate::prelude::*;
+
+/// The functions of an [`Expr`].
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum ExprFunction {
+    Sin,
+    Cos,
+    Abs,
+    Sqrt,
+    Exp,
+}
+
+impl ExprFunction {
+    pub fn from_name(name: &str) -> Option<ExprFunction> {
+        match name {
+            "sin" => Some(ExprFunction::Sin),
+            "cos" => Some(ExprFunction::Cos),
+            "abs" => Some(ExprFunction::Abs),
+            "sqrt" => Some(ExprFunction::Sqrt),
+            "exp" => Some(ExprFunction::Exp),
+            _ => None,
+        }
+    }
+}
+
+/// An expression in the syntax of the exmex displacement expressions which can be evaluated in
+/// any scalar type, over intervals for bounds and with dual numbers for gradients. Supports
+/// numbers, the variables x, y and z, + - * / ^ with integer exponents, and the functions sin,
+/// cos, abs, sqrt and exp.
+#[derive(PartialEq, Clone, Debug)]
+pub enum Expr {
+    Number(f64),
+    Variable(usize),
+    Neg(Box<Expr>),
+    Add(Box<Expr>, Box<Expr>),
+    Sub(Box<Expr>, Box<Expr>),
+    Mul(Box<Expr>, Box<Expr>),
+    Div(Box<Expr>, Box<Expr>),
+    Pow(Box<Expr>, i32),
+    Function(ExprFunction, Box<Expr>),
+}
+
+impl Expr {
+    /// Parses the expression, returns None if it uses anything not supported.
+    pub fn parse(text: &str) -> Option<Expr> {
+        let mut parser = ExprParser {
+            chars: text.chars().filter(|c| !c.is_whitespace()).collect(),
+            pos: 0,
+        };
+        let expr = parser.expr()?;
+        if parser.pos == parser.chars.len() {
+            Some(expr)
+        } else {
+            None
+        }
+    }
+
+    /// Evaluates the expression at the point p of any scalar type.
+    pub fn eval<T: Scalar>(&self, p: &Scalar3<T>) -> T {
+        match self {
+            Expr::Number(v) => T::constant(*v),
+            Expr::Variable(axis) => match axis {
+                0 => p.x,
+                1 => p.y,
+                _ => p.z,
+            },
+            Expr::Neg(a) => -a.eval(p),
+            Expr::Add(a, b) => a.eval(p) + b.eval(p),
+            Expr::Sub(a, b) => a.eval(p) - b.eval(p),
+            Expr::Mul(a, b) => a.eval(p) * b.eval(p),
+            Expr::Div(a, b) => a.eval(p) / b.eval(p),
+            Expr::Pow(a, n) => a.eval(p).powi(*n),
+            Expr::Function(function, a) => {
+                let a = a.eval(p);
+                match function {
+                    ExprFunction::Sin => a.sin(),
+                    ExprFunction::Cos => a.cos(),
+                    ExprFunction::Abs => a.abs(),
+                    ExprFunction::Sqrt => a.sqrt(),
+                    ExprFunction::Exp => a.exp(),
+                }
+            }
+        }
+    }
+}
+
+/// A recursive descent parser for [`Expr`].
+struct ExprParser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl ExprParser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expr(&mut self) -> Option<Expr> {
+        let mut a = self.term()?;
+        loop {
+            if self.eat('+') {
+                a = Expr::Add(Box::new(a), Box::new(self.term()?));
+            } else if self.eat('-') {
+                a = Expr::Sub(Box::new(a), Box::new(self.term()?));
+            } else {
+                return Some(a);
+            }
+        }
+    }
+
+    fn term(&mut self) -> Option<Expr> {
+        let mut a = self.unary()?;
+        loop {
+            if self.eat('*') {
+                a = Expr::Mul(Box::new(a), Box::new(self.unary()?));
+            } else if self.eat('/') {
+                a = Expr::Div(Box::new(a), Box::new(self.unary()?));
+            } else {
+                return Some(a);
+            }
+        }
+    }
+
+    fn unary(&mut self) -> Option<Expr> {
+        if self.eat('-') {
+            Some(Expr::Neg(Box::new(self.unary()?)))
+        } else {
+            self.power()
+        }
+    }
+
+    fn power(&mut self) -> Option<Expr> {
+        let a = self.atom()?;
+        if self.eat('^') {
+            // Only integer exponents have a simple interval extension
+            match self.unary()? {
+                Expr::Number(n) if n.fract() == 0.0 => Some(Expr::Pow(Box::new(a), n as i32)),
+                Expr::Neg(n) => match *n {
+                    Expr::Number(n) if n.fract() == 0.0 => Some(Expr::Pow(Box::new(a), -n as i32)),
+                    _ => None,
+                },
+                _ => None,
+            }
+        } else {
+            Some(a)
+        }
+    }
+
+    fn atom(&mut self) -> Option<Expr> {
+        let c = self.peek()?;
+        if self.eat('(') {
+            let a = self.expr()?;
+            return if self.eat(')') { Some(a) } else { None };
+        }
+
+        let start = self.pos;
+        if c.is_ascii_digit() || c == '.' {
+            while matches!(self.peek(), Some(c) if c.is_ascii_digit() || c == '.') {
+                self.pos += 1;
+            }
+            let text: String = self.chars[start..self.pos].iter().collect();
+            return text.parse().ok().map(Expr::Number);
+        }
+
+        while matches!(self.peek(), Some(c) if c.is_alphanumeric() || c == '_') {
+            self.pos += 1;
+        }
+        let name: String = self.chars[start..self.pos].iter().collect();
+        match name.as_str() {
+            "x" => Some(Expr::Variable(0)),
+            "y" => Some(Expr::Variable(1)),
+            "z" => Some(Expr::Variable(2)),
+            _ => {
+                let function = ExprFunction::from_name(&name)?;
+                if !self.eat('(') {
+                    return None;
+                }
+                let a = self.expr()?;
+                if self.eat(')') {
+                    Some(Expr::Function(function, Box::new(a)))
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,367 @@
+use crate::prelude::*;
+use std::f64::consts::{FRAC_PI_2, PI};
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+/// A closed range of values guaranteed to contain the exact result of a computation over all
+/// inputs in the ranges of its arguments.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct Interval {
+    pub lo: f64,
+    pub hi: f64,
+}
+
+impl Interval {
+    pub fn new(lo: f64, hi: f64) -> Self {
+        Self { lo, hi }
+    }
+
+    pub fn point(v: f64) -> Self {
+        Self { lo: v, hi: v }
+    }
+
+    /// The interval of all values.
+    pub fn entire() -> Self {
+        Self {
+            lo: f64::NEG_INFINITY,
+            hi: f64::INFINITY,
+        }
+    }
+
+    pub fn contains(&self, v: f64) -> bool {
+        self.lo <= v && v <= self.hi
+    }
+
+    /// True if every value is above zero, for a distance this proves a region empty.
+    pub fn is_positive(&self) -> bool {
+        self.lo > 0.0
+    }
+
+    /// True if every value is below zero, for a distance this proves a region full.
+    pub fn is_negative(&self) -> bool {
+        self.hi < 0.0
+    }
+
+    pub fn abs(self) -> Self {
+        if self.lo >= 0.0 {
+            self
+        } else if self.hi <= 0.0 {
+            -self
+        } else {
+            Self::new(0.0, (-self.lo).max(self.hi))
+        }
+    }
+
+    pub fn sqr(self) -> Self {
+        let a = self.abs();
+        Self::new(a.lo * a.lo, a.hi * a.hi)
+    }
+
+    /// The square root, negative values are clamped to zero.
+    pub fn sqrt(self) -> Self {
+        Self::new(self.lo.max(0.0).sqrt(), self.hi.max(0.0).sqrt())
+    }
+
+    pub fn exp(self) -> Self {
+        Self::new(self.lo.exp(), self.hi.exp())
+    }
+
+    pub fn min(self, other: Interval) -> Self {
+        Self::new(self.lo.min(other.lo), self.hi.min(other.hi))
+    }
+
+    pub fn max(self, other: Interval) -> Self {
+        Self::new(self.lo.max(other.lo), self.hi.max(other.hi))
+    }
+
+    pub fn clamp(self, min: f64, max: f64) -> Self {
+        Self::new(self.lo.clamp(min, max), self.hi.clamp(min, max))
+    }
+
+    /// Integer power, even exponents fold the negative range.
+    pub fn powi(self, n: i32) -> Self {
+        if n == 0 {
+            Self::point(1.0)
+        } else if n < 0 {
+            Self::point(1.0) / self.powi(-n)
+        } else if n % 2 == 0 {
+            let a = self.abs();
+            Self::new(a.lo.powi(n), a.hi.powi(n))
+        } else {
+            Self::new(self.lo.powi(n), self.hi.powi(n))
+        }
+    }
+
+    pub fn sin(self) -> Self {
+        let width = self.hi - self.lo;
+        if width.is_nan() || width >= 2.0 * PI {
+            return Self::new(-1.0, 1.0);
+        }
+
+        let a = self.lo.sin();
+        let b = self.hi.sin();
+        let mut result = Self::new(a.min(b), a.max(b));
+
+        if contains_period(self, FRAC_PI_2) {
+            result.hi = 1.0;
+        }
+        if contains_period(self, -FRAC_PI_2) {
+            result.lo = -1.0;
+        }
+        result
+    }
+
+    pub fn cos(self) -> Self {
+        (self + FRAC_PI_2).sin()
+    }
+}
+
+/// True if the interval contains phase + 2πk for any integer k.
+fn contains_period(i: Interval, phase: f64) -> bool {
+    let k = ((i.lo - phase) / (2.0 * PI)).ceil();
+    phase + k * 2.0 * PI <= i.hi
+}
+
+impl Add for Interval {
+    type Output = Interval;
+    fn add(self, other: Interval) -> Interval {
+        Interval::new(self.lo + other.lo, self.hi + other.hi)
+    }
+}
+
+impl Add<f64> for Interval {
+    type Output = Interval;
+    fn add(self, other: f64) -> Interval {
+        Interval::new(self.lo + other, self.hi + other)
+    }
+}
+
+impl Sub for Interval {
+    type Output = Interval;
+    fn sub(self, other: Interval) -> Interval {
+        Interval::new(self.lo - other.hi, self.hi - other.lo)
+    }
+}
+
+impl Sub<f64> for Interval {
+    type Output = Interval;
+    fn sub(self, other: f64) -> Interval {
+        Interval::new(self.lo - other, self.hi - other)
+    }
+}
+
+impl Mul for Interval {
+    type Output = Interval;
+    fn mul(self, other: Interval) -> Interval {
+        let a = self.lo * other.lo;
+        let b = self.lo * other.hi;
+        let c = self.hi * other.lo;
+        let d = self.hi * other.hi;
+        Interval::new(a.min(b).min(c.min(d)), a.max(b).max(c.max(d)))
+    }
+}
+
+impl Mul<f64> for Interval {
+    type Output = Interval;
+    fn mul(self, other: f64) -> Interval {
+        let a = self.lo * other;
+        let b = self.hi * other;
+        Interval::new(a.min(b), a.max(b))
+    }
+}
+
+impl Div for Interval {
+    type Output = Interval;
+    fn div(self, other: Interval) -> Interval {
+        if other.contains(0.0) {
+            Interval::entire()
+        } else {
+            self * Interval::new(1.0 / other.hi, 1.0 / other.lo)
+        }
+    }
+}
+
+impl Neg for Interval {
+    type Output = Interval;
+    fn neg(self) -> Interval {
+        Interval::new(-self.hi, -self.lo)
+    }
+}
+
+impl Scalar for Interval {
+    fn constant(v: f64) -> Self {
+        Interval::point(v)
+    }
+
+    fn abs(self) -> Self {
+        Interval::abs(self)
+    }
+
+    fn sqrt(self) -> Self {
+        Interval::sqrt(self)
+    }
+
+    fn min(self, other: Self) -> Self {
+        Interval::min(self, other)
+    }
+
+    fn max(self, other: Self) -> Self {
+        Interval::max(self, other)
+    }
+
+    fn clamp(self, min: f64, max: f64) -> Self {
+        Interval::clamp(self, min, max)
+    }
+
+    fn powi(self, n: i32) -> Self {
+        Interval::powi(self, n)
+    }
+
+    fn sin(self) -> Self {
+        Interval::sin(self)
+    }
+
+    fn cos(self) -> Self {
+        Interval::cos(self)
+    }
+
+    fn exp(self) -> Self {
+        Interval::exp(self)
+    }
+
+    /// Unlike self * self the square is never negative.
+    fn sqr(self) -> Self {
+        Interval::sqr(self)
+    }
+}
+
+/// A box of points given by one interval per axis.
+pub type Interval3 = Scalar3<Interval>;
+
+impl Interval3 {
+    pub fn from_aabb(b: &Aabb) -> Self {
+        Self::new(
+            Interval::new(b.min.x, b.max.x),
+            Interval::new(b.min.y, b.max.y),
+            Interval::new(b.min.z, b.max.z),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn objects() -> Vec<Object> {
+        let primitives = [
+            Primitive::Sphere { radius: 1.0 },
+            Primitive::Box {
+                size: vec3d(0.8, 0.5, 1.2),
+                rounding: 0.1,
+            },
+            Primitive::Torus {
+                radius: 1.0,
+                thickness: 0.3,
+            },
+            Primitive::Capsule {
+                height: 1.5,
+                radius: 0.4,
+            },
+        ];
+
+        primitives
+            .iter()
+            .enumerate()
+            .map(|(i, primitive)| {
+                let mut object = Object::new();
+                object.primitive = *primitive;
+                object.transform.position = vec3d(0.3 * i as f64, -0.2, 0.1);
+                object.transform.rotation = vec3d(15.0 * i as f64, 30.0, -20.0);
+                object.transform.scale = 0.5 + 0.4 * i as f64;
+                object.smoothing = 0.3;
+                object
+            })
+            .collect()
+    }
+
+    /// Random boxes around the objects together with points inside each box.
+    fn boxes(rng: &mut Pcg32) -> Vec<(Aabb, Vec<Vec3d>)> {
+        let mut point = |scale: f64| {
+            vec3d(
+                (rng.next_f64() - 0.5) * scale,
+                (rng.next_f64() - 0.5) * scale,
+                (rng.next_f64() - 0.5) * scale,
+            )
+        };
+
+        (0..200)
+            .map(|_| {
+                let mut b = Aabb::empty();
+                let corner = point(4.0);
+                b.extend(corner);
+                b.extend(corner + point(1.5));
+
+                let size = b.size();
+                let samples = (0..20)
+                    .map(|_| {
+                        let t = point(1.0) + vec3d(0.5, 0.5, 0.5);
+                        b.min + vec3d(size.x * t.x, size.y * t.y, size.z * t.z)
+                    })
+                    .collect();
+                (b, samples)
+            })
+            .collect()
+    }
+
+    /// True if the interval contains v up to rounding.
+    fn encloses(i: Interval, v: f64) -> bool {
+        i.lo - 1e-9 <= v && v <= i.hi + 1e-9
+    }
+
+    #[test]
+    fn object_intervals_enclose_samples() {
+        let mut rng = Pcg32::new(3);
+        for object in objects() {
+            for (b, samples) in boxes(&mut rng) {
+                let range = object.distance_interval(&b);
+                for p in samples {
+                    let d = object.distance(p);
+                    assert!(
+                        encloses(range, d),
+                        "{:?}: {} not in {:?}",
+                        object.primitive,
+                        d,
+                        range
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn csg_intervals_enclose_samples() {
+        let mut rng = Pcg32::new(5);
+        let objects = objects();
+        for op in [CsgOp::Union, CsgOp::Subtraction, CsgOp::Intersection] {
+            for k in [0.0, 0.3] {
+                for pair in objects.windows(2) {
+                    for (b, samples) in boxes(&mut rng) {
+                        let a = pair[0].distance_interval(&b);
+                        let range = op.apply_interval(Some(a), pair[1].distance_interval(&b), k);
+                        let range = range.unwrap();
+                        for p in samples {
+                            let d = op.apply_distance(pair[0].distance(p), pair[1].distance(p), k);
+                            assert!(
+                                encloses(range, d),
+                                "{:?} {}: {} not in {:?}",
+                                op,
+                                k,
+                                d,
+                                range
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,65 @@
+use crate::prelude::*;
+
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy, Debug)]
+pub enum LightType {
+    Point,
+    Directional,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct Light {
+    pub name: String,
+    pub id: Uuid,
+
+    pub light_type: LightType,
+
+    /// The position of a point light or the direction towards a directional light.
+    pub position: Vec3d,
+    pub color: Vec3d,
+    pub intensity: f64,
+
+    /// Penumbra factor of the soft shadow, higher values give harder shadows.
+    pub shadow_k: f64,
+}
+
+impl Default for Light {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Light {
+    pub fn new() -> Self {
+        Self {
+            name: str!("Key Light"),
+            id: Uuid::new_v4(),
+
+            light_type: LightType::Directional,
+            position: vec3d(0.6, 1.0, 0.8),
+            color: Vec3d::one(),
+            intensity: 1.0,
+
+            shadow_k: 8.0,
+        }
+    }
+
+    /// Returns the normalized direction from p towards the light and the distance to the light.
+    pub fn direction_from(&self, p: Vec3d) -> (Vec3d, f64) {
+        match self.light_type {
+            LightType::Point => {
+                let v = self.position - p;
+                let distance = length(v);
+                (v / distance, distance)
+            }
+            LightType::Directional => (normalize(self.position), f64::MAX),
+        }
+    }
+
+    /// Returns the radiance arriving from the light over the given distance.
+    pub fn radiance(&self, distance: f64) -> Vec3d {
+        match self.light_type {
+            LightType::Point => self.color * (self.intensity / (distance * distance).max(0.0001)),
+            LightType::Directional => self.color * self.intensity,
+        }
+    }
+}
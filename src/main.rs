@@ -1,28 +1,77 @@
 use theframework::*;
 
+pub mod bounds;
+pub mod brdf;
+pub mod bvh;
+pub mod cache;
 pub mod camera;
+pub mod cli;
+pub mod color;
+pub mod csg;
+pub mod dual;
 pub mod editor;
+pub mod environment;
+pub mod export;
+pub mod expr;
+pub mod interval;
+pub mod light;
+pub mod material;
 pub mod misc;
 pub mod object;
+pub mod packet;
 pub mod panel;
+pub mod path_tracer;
 pub mod point;
+pub mod primitive;
 pub mod project;
 pub mod renderview;
+pub mod sampler;
+pub mod scalar;
+pub mod settings;
 pub mod sidebar;
+pub mod single;
+pub mod sky;
+pub mod tiles;
 pub mod tracer;
+pub mod transform;
+pub mod worker;
 
 pub mod prelude {
+    pub use crate::bounds::*;
+    pub use crate::brdf::*;
+    pub use crate::bvh::*;
+    pub use crate::cache::*;
     pub use crate::camera::*;
+    pub use crate::color::*;
+    pub use crate::csg::*;
+    pub use crate::dual::*;
     pub use crate::editor::EditorContext;
+    pub use crate::environment::*;
+    pub use crate::export::*;
+    pub use crate::expr::*;
+    pub use crate::interval::*;
+    pub use crate::light::*;
+    pub use crate::material::*;
     pub use crate::misc::*;
     pub use crate::object::*;
+    pub use crate::packet::*;
     pub use crate::panel::*;
+    pub use crate::path_tracer::*;
     pub use crate::point::*;
+    pub use crate::primitive::*;
     pub use crate::project::*;
     pub use crate::renderview::*;
+    pub use crate::sampler::*;
+    pub use crate::scalar::*;
+    pub use crate::tiles::*;
     pub use crate::tracer::*;
+    pub use crate::transform::*;
+    pub use crate::worker::*;
 
+    pub use crate::settings::*;
     pub use crate::sidebar::*;
+    pub use crate::single::*;
+    pub use crate::sky::*;
     pub use ::serde::{Deserialize, Serialize};
     pub use maths_rs::prelude::*;
     pub use rustc_hash::*;
@@ -36,6 +85,10 @@ use crate::editor::Editor;
 fn main() {
     // std::env::set_var("RUST_BACKTRACE", "1");
 
+    if cli::run() {
+        return;
+    }
+
     let editor = Editor::new();
     let mut app = TheApp::new();
 
@@ -0,0 +1,50 @@
+use crate::prelude::*;
+
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct Material {
+    pub color: Vec3d,
+    pub roughness: f64,
+    pub metallic: f64,
+    pub emission: Vec3d,
+    /// The fraction of light passing through the surface, tinted by the color.
+    #[serde(default)]
+    pub transmission: f64,
+    /// The index of refraction of transparent materials.
+    #[serde(default = "default_ior")]
+    pub ior: f64,
+}
+
+fn default_ior() -> f64 {
+    1.5
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Material {
+    pub fn new() -> Self {
+        Self {
+            color: vec3d(0.8, 0.8, 0.8),
+            roughness: 0.5,
+            metallic: 0.0,
+            emission: Vec3d::zero(),
+            transmission: 0.0,
+            ior: default_ior(),
+        }
+    }
+
+    /// Linearly blends this material with the other one, t = 1.0 returns the other material.
+    pub fn mix(&self, other: &Material, t: f64) -> Material {
+        Material {
+            color: self.color + (other.color - self.color) * t,
+            roughness: self.roughness + (other.roughness - self.roughness) * t,
+            metallic: self.metallic + (other.metallic - self.metallic) * t,
+            emission: self.emission + (other.emission - self.emission) * t,
+            transmission: self.transmission + (other.transmission - self.transmission) * t,
+            ior: self.ior + (other.ior - self.ior) * t,
+        }
+    }
+}
@@ -1,8 +1,10 @@
+use rayon::prelude::*;
 use std::time::{Duration, Instant};
 use theframework::prelude::*;
 
-#[derive(Serialize, Deserialize, PartialEq, Clone, Copy, Debug)]
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy, Debug, Default)]
 pub enum CameraMode {
+    #[default]
     Pinhole,
     Orthogonal,
 }
@@ -18,6 +20,13 @@ pub struct Ray {
     pub sign_x: usize,
     pub sign_y: usize,
     pub sign_z: usize,
+
+    /// The width of the pixel footprint at the origin of the ray.
+    #[serde(default)]
+    pub footprint: f64,
+    /// The growth of the pixel footprint per unit of distance, the pixel cone angle.
+    #[serde(default)]
+    pub spread: f64,
 }
 
 impl Ray {
@@ -30,9 +39,25 @@ impl Ray {
             sign_x: (d.x < 0.0) as usize,
             sign_y: (d.y < 0.0) as usize,
             sign_z: (d.z < 0.0) as usize,
+
+            footprint: 0.0,
+            spread: 0.0,
         }
     }
 
+    /// Sets the pixel footprint of a camera ray.
+    pub fn with_footprint(mut self, footprint: f64, spread: f64) -> Self {
+        self.footprint = footprint;
+        self.spread = spread;
+        self
+    }
+
+    /// Returns the width of the pixel footprint at the given distance, 0.0 for rays which do not
+    /// belong to a pixel.
+    pub fn footprint_at(&self, d: f64) -> f64 {
+        self.footprint + self.spread * d
+    }
+
     /// Returns the position on the ray at the given distance
     pub fn at(&self, d: f64) -> Vec3d {
         self.o + self.d * d
@@ -85,3 +110,63 @@ impl UpdateTracker {
     //     //println!("FPS: {}", fps);
     // }
 }
+
+/// Scales the source buffer to the size of the destination buffer by repeating pixels.
+pub fn upscale_nearest(src: &TheRGBABuffer, dst: &mut TheRGBABuffer) {
+    let src_width = src.dim().width as usize;
+    let src_height = src.dim().height as usize;
+    let dst_width = dst.dim().width as usize;
+    let dst_height = dst.dim().height as usize;
+
+    if src_width == 0 || src_height == 0 || dst_width == 0 {
+        return;
+    }
+
+    let src_pixels = src.pixels();
+    dst.pixels_mut()
+        .par_chunks_exact_mut(dst_width * 4)
+        .enumerate()
+        .for_each(|(y, line)| {
+            let sy = (y * src_height / dst_height).min(src_height - 1);
+            for (x, pixel) in line.chunks_exact_mut(4).enumerate() {
+                let sx = (x * src_width / dst_width).min(src_width - 1);
+                let i = (sy * src_width + sx) * 4;
+                pixel.copy_from_slice(&src_pixels[i..i + 4]);
+            }
+        });
+}
+
+/// Copies the source buffer into the destination buffer with its top left corner at x, y.
+pub fn copy_tile(src: &TheRGBABuffer, dst: &mut TheRGBABuffer, x: usize, y: usize) {
+    let src_width = src.dim().width as usize;
+    let dst_width = dst.dim().width as usize;
+    copy_pixels(src.pixels(), src_width, dst.pixels_mut(), dst_width, x, y);
+}
+
+/// Copies the RGBA source pixels into the RGBA destination pixels with their top left corner at
+/// x, y, clipped to the destination. Used for both 8-bit and float buffers.
+pub fn copy_pixels<T: Copy>(
+    src: &[T],
+    src_width: usize,
+    dst: &mut [T],
+    dst_width: usize,
+    x: usize,
+    y: usize,
+) {
+    if src_width == 0 || dst_width == 0 {
+        return;
+    }
+
+    let src_height = src.len() / (src_width * 4);
+    let dst_height = dst.len() / (dst_width * 4);
+    if x >= dst_width || y >= dst_height {
+        return;
+    }
+
+    let width = src_width.min(dst_width - x) * 4;
+    for row in 0..src_height.min(dst_height - y) {
+        let s = row * src_width * 4;
+        let d = ((y + row) * dst_width + x) * 4;
+        dst[d..d + width].copy_from_slice(&src[s..s + width]);
+    }
+}
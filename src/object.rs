@@ -6,6 +6,20 @@ pub struct Object {
     pub id: Uuid,
 
     pub points: Vec<Point>,
+
+    #[serde(default)]
+    pub primitive: Primitive,
+    #[serde(default)]
+    pub transform: Transform,
+
+    #[serde(default)]
+    pub op: CsgOp,
+    /// The blend radius of the CSG operation, 0.0 for hard edges.
+    #[serde(default)]
+    pub smoothing: f64,
+
+    #[serde(default)]
+    pub material: Material,
 }
 
 impl Default for Object {
@@ -34,7 +48,52 @@ impl Object {
             id: Uuid::new_v4(),
 
             points,
+
+            primitive: Primitive::default(),
+            transform: Transform::default(),
+
+            op: CsgOp::Union,
+            smoothing: 0.0,
+
+            material: Material::default(),
+        }
+    }
+
+    /// Returns the signed distance of the world space point p to the object.
+    pub fn distance(&self, p: Vec3d) -> f64 {
+        self.eval(&Scalar3::constant(p))
+    }
+
+    /// Returns the signed distances of the world space points of the packet.
+    pub fn distance_packet(&self, p: &Vec3Packet) -> F64x {
+        self.eval(p)
+    }
+
+    /// Returns the signed distance of the world space point p in any scalar type.
+    #[inline(always)]
+    pub fn eval<T: Scalar>(&self, p: &Scalar3<T>) -> T {
+        let local = self.transform.to_local_scalar(p);
+        self.primitive.eval(&local) * T::constant(self.transform.scale)
+    }
+
+    /// Returns the range of the signed distance over the world space box.
+    pub fn distance_interval(&self, bounds: &Aabb) -> Interval {
+        self.eval(&Interval3::from_aabb(bounds))
+    }
+
+    /// Returns the world space bounds of the object, including the reach of smooth operations.
+    pub fn bounds(&self) -> Aabb {
+        let local = self.primitive.bounds();
+        let mut bounds = Aabb::empty();
+        for i in 0..8 {
+            let corner = vec3d(
+                if i & 1 == 0 { local.min.x } else { local.max.x },
+                if i & 2 == 0 { local.min.y } else { local.max.y },
+                if i & 4 == 0 { local.min.z } else { local.max.z },
+            );
+            bounds.extend(self.transform.to_world(corner));
         }
+        bounds.expand(self.smoothing.max(0.0))
     }
 
     /*
@@ -0,0 +1,195 @@
+use crate::prelude::*;
+use std::ops::{Add, Div, Index, IndexMut, Mul, Neg, Sub};
+
+/// The number of points evaluated together in a packet.
+pub const LANES: usize = 4;
+
+/// One value per lane of a packet. All operations work lane-wise on adjacent values so that
+/// they vectorize.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct F64x(pub [f64; LANES]);
+
+impl F64x {
+    /// The same value in every lane.
+    pub fn splat(v: f64) -> Self {
+        Self([v; LANES])
+    }
+}
+
+impl Index<usize> for F64x {
+    type Output = f64;
+    fn index(&self, lane: usize) -> &f64 {
+        &self.0[lane]
+    }
+}
+
+impl IndexMut<usize> for F64x {
+    fn index_mut(&mut self, lane: usize) -> &mut f64 {
+        &mut self.0[lane]
+    }
+}
+
+impl Add for F64x {
+    type Output = F64x;
+    #[inline(always)]
+    fn add(self, other: F64x) -> F64x {
+        lanes(|i| self[i] + other[i])
+    }
+}
+
+impl Sub for F64x {
+    type Output = F64x;
+    #[inline(always)]
+    fn sub(self, other: F64x) -> F64x {
+        lanes(|i| self[i] - other[i])
+    }
+}
+
+impl Mul for F64x {
+    type Output = F64x;
+    #[inline(always)]
+    fn mul(self, other: F64x) -> F64x {
+        lanes(|i| self[i] * other[i])
+    }
+}
+
+impl Div for F64x {
+    type Output = F64x;
+    #[inline(always)]
+    fn div(self, other: F64x) -> F64x {
+        lanes(|i| self[i] / other[i])
+    }
+}
+
+impl Neg for F64x {
+    type Output = F64x;
+    #[inline(always)]
+    fn neg(self) -> F64x {
+        lanes(|i| -self[i])
+    }
+}
+
+impl Scalar for F64x {
+    #[inline(always)]
+    fn constant(v: f64) -> Self {
+        F64x::splat(v)
+    }
+
+    #[inline(always)]
+    fn abs(self) -> Self {
+        lanes(|i| self[i].abs())
+    }
+
+    #[inline(always)]
+    fn sqrt(self) -> Self {
+        lanes(|i| self[i].sqrt())
+    }
+
+    #[inline(always)]
+    fn min(self, other: Self) -> Self {
+        lanes(|i| self[i].min(other[i]))
+    }
+
+    #[inline(always)]
+    fn max(self, other: Self) -> Self {
+        lanes(|i| self[i].max(other[i]))
+    }
+
+    #[inline(always)]
+    fn clamp(self, min: f64, max: f64) -> Self {
+        lanes(|i| self[i].clamp(min, max))
+    }
+
+    #[inline(always)]
+    fn powi(self, n: i32) -> Self {
+        lanes(|i| self[i].powi(n))
+    }
+
+    #[inline(always)]
+    fn sin(self) -> Self {
+        lanes(|i| self[i].sin())
+    }
+
+    #[inline(always)]
+    fn cos(self) -> Self {
+        lanes(|i| self[i].cos())
+    }
+
+    #[inline(always)]
+    fn exp(self) -> Self {
+        lanes(|i| self[i].exp())
+    }
+}
+
+/// Points in structure of arrays layout, the lanes of each component are adjacent in memory.
+pub type Vec3Packet = Scalar3<F64x>;
+
+impl Vec3Packet {
+    /// Creates the packet from the point of every lane.
+    pub fn from_fn(f: impl Fn(usize) -> Vec3d) -> Self {
+        let points: [Vec3d; LANES] = std::array::from_fn(f);
+        Self::new(
+            F64x(points.map(|p| p.x)),
+            F64x(points.map(|p| p.y)),
+            F64x(points.map(|p| p.z)),
+        )
+    }
+
+    /// The point of the given lane.
+    pub fn get(&self, lane: usize) -> Vec3d {
+        vec3d(self.x[lane], self.y[lane], self.z[lane])
+    }
+}
+
+/// Creates a lane value from a function of the lane index.
+#[inline(always)]
+pub fn lanes(f: impl Fn(usize) -> f64) -> F64x {
+    F64x(std::array::from_fn(f))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packets_match_single_points() {
+        let primitives = [
+            Primitive::Sphere { radius: 1.0 },
+            Primitive::Box {
+                size: vec3d(0.8, 0.5, 1.2),
+                rounding: 0.1,
+            },
+            Primitive::Torus {
+                radius: 1.0,
+                thickness: 0.3,
+            },
+            Primitive::Capsule {
+                height: 1.5,
+                radius: 0.4,
+            },
+        ];
+
+        let mut rng = Pcg32::new(17);
+        for primitive in primitives {
+            let mut object = Object::new();
+            object.primitive = primitive;
+            object.transform.rotation = vec3d(10.0, 45.0, -30.0);
+            object.transform.scale = 0.8;
+
+            for _ in 0..100 {
+                let points: [Vec3d; LANES] = std::array::from_fn(|_| {
+                    vec3d(
+                        rng.next_f64() * 4.0 - 2.0,
+                        rng.next_f64() * 4.0 - 2.0,
+                        rng.next_f64() * 4.0 - 2.0,
+                    )
+                });
+                let p = Vec3Packet::from_fn(|lane| points[lane]);
+                let d = object.distance_packet(&p);
+                for lane in 0..LANES {
+                    assert_eq!(d[lane], object.distance(p.get(lane)));
+                }
+            }
+        }
+    }
+}
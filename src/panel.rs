@@ -18,8 +18,10 @@ impl Panel {
         &mut self,
         _ui: &mut TheUI,
         _ctx: &mut TheContext,
-        _project: &mut Project,
+        project: &mut Project,
     ) -> TheCanvas {
+        let settings = &project.settings;
+
         let mut canvas = TheCanvas::new();
 
         // Toolbar
@@ -54,6 +56,16 @@ impl Panel {
         // toolbar_hlayout.add_widget(Box::new(clear_mask_button));
         // toolbar_hlayout.set_reverse_index(Some(1));
 
+        let mut export_button = TheTraybarButton::new(TheId::named("Export Passes"));
+        export_button.set_text(str!("Export Passes"));
+        export_button.set_status_text("Export every render pass as its own image.");
+        toolbar_hlayout.add_widget(Box::new(export_button));
+
+        let mut load_hdr_button = TheTraybarButton::new(TheId::named("Load HDR"));
+        load_hdr_button.set_text(str!("Load HDR"));
+        load_hdr_button.set_status_text("Load an equirectangular .hdr file as environment light.");
+        toolbar_hlayout.add_widget(Box::new(load_hdr_button));
+
         toolbar_canvas.set_layout(toolbar_hlayout);
 
         canvas.set_top(toolbar_canvas);
@@ -62,8 +74,227 @@ impl Panel {
 
         let mut center_canvas = TheCanvas::default();
 
-        let mut text_layout = TheTextLayout::new(TheId::named("TileFX Settings"));
+        let mut text_layout = TheTextLayout::new(TheId::named("Render Settings"));
         text_layout.limiter_mut().set_max_width(300);
+
+        let mut shadows_check = TheCheckButton::new(TheId::named("Shadows"));
+        if settings.shadows {
+            shadows_check.set_state(TheWidgetState::Selected);
+        }
+        shadows_check.set_status_text("Trace soft shadows towards the lights.");
+        text_layout.add_pair("Shadows".to_string(), Box::new(shadows_check));
+
+        let mut path_tracing_check = TheCheckButton::new(TheId::named("Path Tracing"));
+        path_tracing_check.set_status_text("Progressively path trace the scene for final renders.");
+        text_layout.add_pair("Path Tracing".to_string(), Box::new(path_tracing_check));
+
+        let mut iso_value_edit = TheTextLineEdit::new(TheId::named("Iso Value"));
+        iso_value_edit.set_text(settings.iso_value.to_string());
+        iso_value_edit.set_range(TheValue::RangeF32(0.000001..=0.1));
+        iso_value_edit.set_status_text("The hit distance of rays without a pixel footprint.");
+        text_layout.add_pair("Iso Value".to_string(), Box::new(iso_value_edit));
+
+        let mut pixel_threshold_edit = TheTextLineEdit::new(TheId::named("Pixel Threshold"));
+        pixel_threshold_edit.set_text(settings.pixel_threshold.to_string());
+        pixel_threshold_edit.set_range(TheValue::RangeF32(0.0..=2.0));
+        pixel_threshold_edit
+            .set_status_text("The hit threshold in pixels, 0 uses the fixed iso value.");
+        text_layout.add_pair(
+            "Pixel Threshold".to_string(),
+            Box::new(pixel_threshold_edit),
+        );
+
+        let mut max_distance_edit = TheTextLineEdit::new(TheId::named("Max Distance"));
+        max_distance_edit.set_text(settings.t_max.to_string());
+        max_distance_edit.set_range(TheValue::RangeF32(0.1..=1000.0));
+        max_distance_edit.set_status_text("The maximum distance a ray travels.");
+        text_layout.add_pair("Max Distance".to_string(), Box::new(max_distance_edit));
+
+        let mut max_steps_edit = TheTextLineEdit::new(TheId::named("Max Steps"));
+        max_steps_edit.set_text(settings.max_steps.to_string());
+        max_steps_edit.set_range(TheValue::RangeI32(1..=2000));
+        max_steps_edit.set_status_text("The maximum number of sphere tracing steps per ray.");
+        text_layout.add_pair("Max Steps".to_string(), Box::new(max_steps_edit));
+
+        let mut step_size_edit = TheTextLineEdit::new(TheId::named("Step Size"));
+        step_size_edit.set_text(settings.step_size.to_string());
+        step_size_edit.set_range(TheValue::RangeF32(0.1..=1.0));
+        step_size_edit.set_status_text("Scales each step, lower values help distorted fields.");
+        text_layout.add_pair("Step Size".to_string(), Box::new(step_size_edit));
+
+        let mut method_dropdown = TheDropdownMenu::new(TheId::named("March Method"));
+        for method in MarchMethod::all() {
+            method_dropdown.add_option(method.name().to_string());
+        }
+        method_dropdown.set_selected_index(option_index(&MarchMethod::all(), &settings.method));
+        method_dropdown.set_status_text("The sphere tracing loop used to find surfaces.");
+        text_layout.add_pair("March Method".to_string(), Box::new(method_dropdown));
+
+        let mut relaxation_edit = TheTextLineEdit::new(TheId::named("Relaxation"));
+        relaxation_edit.set_text(settings.relaxation.to_string());
+        relaxation_edit.set_range(TheValue::RangeF32(1.0..=1.99));
+        relaxation_edit.set_status_text("The step multiplier of over-relaxed sphere tracing.");
+        text_layout.add_pair("Relaxation".to_string(), Box::new(relaxation_edit));
+
+        let mut refine_check = TheCheckButton::new(TheId::named("Refine Hits"));
+        if settings.refine {
+            refine_check.set_state(TheWidgetState::Selected);
+        }
+        refine_check.set_status_text("Bisect hits which overshot the surface.");
+        text_layout.add_pair("Refine Hits".to_string(), Box::new(refine_check));
+
+        let mut packets_check = TheCheckButton::new(TheId::named("Packets"));
+        if settings.packets {
+            packets_check.set_state(TheWidgetState::Selected);
+        }
+        packets_check.set_status_text("March neighboring rays together, used without refinement.");
+        text_layout.add_pair("Packets".to_string(), Box::new(packets_check));
+
+        let mut cache_check = TheCheckButton::new(TheId::named("Distance Cache"));
+        if settings.distance_cache {
+            cache_check.set_state(TheWidgetState::Selected);
+        }
+        cache_check.set_status_text("Bake objects which are not being edited for faster previews.");
+        text_layout.add_pair("Distance Cache".to_string(), Box::new(cache_check));
+
+        let mut precision_dropdown = TheDropdownMenu::new(TheId::named("Precision"));
+        for precision in Precision::all() {
+            precision_dropdown.add_option(precision.name().to_string());
+        }
+        precision_dropdown.set_selected_index(option_index(&Precision::all(), &settings.precision));
+        precision_dropdown.set_status_text("Single precision speeds up previews.");
+        text_layout.add_pair("Precision".to_string(), Box::new(precision_dropdown));
+
+        let mut antialiasing_dropdown = TheDropdownMenu::new(TheId::named("Anti-Aliasing"));
+        for antialiasing in AntiAliasing::all() {
+            antialiasing_dropdown.add_option(antialiasing.name().to_string());
+        }
+        antialiasing_dropdown
+            .set_selected_index(option_index(&AntiAliasing::all(), &settings.antialiasing));
+        antialiasing_dropdown
+            .set_status_text("Cone anti-aliasing smooths edges with one ray per pixel.");
+        text_layout.add_pair("Anti-Aliasing".to_string(), Box::new(antialiasing_dropdown));
+
+        let mut aa_edit = TheTextLineEdit::new(TheId::named("AA"));
+        aa_edit.set_text(settings.aa.to_string());
+        aa_edit.set_range(TheValue::RangeI32(1..=8));
+        aa_edit.set_status_text("The number of samples per pixel along each axis.");
+        text_layout.add_pair("AA".to_string(), Box::new(aa_edit));
+
+        let mut sampling_dropdown = TheDropdownMenu::new(TheId::named("Sampling"));
+        for pattern in SamplePattern::all() {
+            sampling_dropdown.add_option(pattern.name().to_string());
+        }
+        sampling_dropdown
+            .set_selected_index(option_index(&SamplePattern::all(), &settings.sampling));
+        sampling_dropdown.set_status_text("Where the supersamples are placed inside a pixel.");
+        text_layout.add_pair("Sampling".to_string(), Box::new(sampling_dropdown));
+
+        let mut adaptive_check = TheCheckButton::new(TheId::named("Adaptive"));
+        if settings.adaptive {
+            adaptive_check.set_state(TheWidgetState::Selected);
+        }
+        adaptive_check.set_status_text("Only supersample pixels whose first samples disagree.");
+        text_layout.add_pair("Adaptive".to_string(), Box::new(adaptive_check));
+
+        let mut adaptive_threshold_edit = TheTextLineEdit::new(TheId::named("Adaptive Threshold"));
+        adaptive_threshold_edit.set_text(settings.adaptive_threshold.to_string());
+        adaptive_threshold_edit.set_range(TheValue::RangeF32(0.0..=1.0));
+        adaptive_threshold_edit
+            .set_status_text("The color difference above which a pixel takes all samples.");
+        text_layout.add_pair(
+            "Adaptive Threshold".to_string(),
+            Box::new(adaptive_threshold_edit),
+        );
+
+        let mut max_depth_edit = TheTextLineEdit::new(TheId::named("Max Depth"));
+        max_depth_edit.set_text(settings.max_depth.to_string());
+        max_depth_edit.set_range(TheValue::RangeI32(0..=8));
+        max_depth_edit.set_status_text("The number of reflection and refraction bounces.");
+        text_layout.add_pair("Max Depth".to_string(), Box::new(max_depth_edit));
+
+        let mut max_samples_edit = TheTextLineEdit::new(TheId::named("Max Samples"));
+        max_samples_edit.set_text(settings.max_samples.to_string());
+        max_samples_edit.set_range(TheValue::RangeI32(1..=65536));
+        max_samples_edit.set_status_text("The samples per pixel after which path tracing stops.");
+        text_layout.add_pair("Max Samples".to_string(), Box::new(max_samples_edit));
+
+        let mut exposure_edit = TheTextLineEdit::new(TheId::named("Exposure"));
+        exposure_edit.set_text(settings.exposure.to_string());
+        exposure_edit.set_range(TheValue::RangeF32(-10.0..=10.0));
+        exposure_edit.set_status_text("The exposure of the shaded pass in stops.");
+        text_layout.add_pair("Exposure".to_string(), Box::new(exposure_edit));
+
+        let mut tone_mapping_dropdown = TheDropdownMenu::new(TheId::named("Tone Mapping"));
+        for tone_mapping in ToneMapping::all() {
+            tone_mapping_dropdown.add_option(tone_mapping.name().to_string());
+        }
+        tone_mapping_dropdown
+            .set_selected_index(option_index(&ToneMapping::all(), &settings.tone_mapping));
+        tone_mapping_dropdown.set_status_text("Maps bright colors into the displayable range.");
+        text_layout.add_pair("Tone Mapping".to_string(), Box::new(tone_mapping_dropdown));
+
+        let mut ao_steps_edit = TheTextLineEdit::new(TheId::named("AO Steps"));
+        ao_steps_edit.set_text(settings.ao_steps.to_string());
+        ao_steps_edit.set_range(TheValue::RangeI32(0..=32));
+        ao_steps_edit.set_status_text("The number of ambient occlusion samples along the normal.");
+        text_layout.add_pair("AO Steps".to_string(), Box::new(ao_steps_edit));
+
+        let mut ao_strength_edit = TheTextLineEdit::new(TheId::named("AO Strength"));
+        ao_strength_edit.set_text(settings.ao_strength.to_string());
+        ao_strength_edit.set_range(TheValue::RangeF32(0.0..=10.0));
+        ao_strength_edit.set_status_text("The strength of the ambient occlusion.");
+        text_layout.add_pair("AO Strength".to_string(), Box::new(ao_strength_edit));
+
+        let mut env_rotation_edit = TheTextLineEdit::new(TheId::named("Env Rotation"));
+        env_rotation_edit.set_text(
+            project
+                .environment
+                .as_ref()
+                .map_or(0.0, |e| e.rotation)
+                .to_string(),
+        );
+        env_rotation_edit.set_range(TheValue::RangeF32(0.0..=360.0));
+        env_rotation_edit.set_status_text("The rotation of the environment around the y axis.");
+        text_layout.add_pair("Env Rotation".to_string(), Box::new(env_rotation_edit));
+
+        let mut env_intensity_edit = TheTextLineEdit::new(TheId::named("Env Intensity"));
+        env_intensity_edit.set_text(
+            project
+                .environment
+                .as_ref()
+                .map_or(1.0, |e| e.intensity)
+                .to_string(),
+        );
+        env_intensity_edit.set_range(TheValue::RangeF32(0.0..=100.0));
+        env_intensity_edit.set_status_text("The intensity of the environment light.");
+        text_layout.add_pair("Env Intensity".to_string(), Box::new(env_intensity_edit));
+
+        let mut sky_check = TheCheckButton::new(TheId::named("Sky"));
+        if project.sky.enabled {
+            sky_check.set_state(TheWidgetState::Selected);
+        }
+        sky_check.set_status_text("Use the procedural sky and sun when no HDR is loaded.");
+        text_layout.add_pair("Sky".to_string(), Box::new(sky_check));
+
+        let mut sun_elevation_edit = TheTextLineEdit::new(TheId::named("Sun Elevation"));
+        sun_elevation_edit.set_text(project.sky.sun_elevation.to_string());
+        sun_elevation_edit.set_range(TheValue::RangeF32(-10.0..=90.0));
+        sun_elevation_edit.set_status_text("The elevation of the sun above the horizon.");
+        text_layout.add_pair("Sun Elevation".to_string(), Box::new(sun_elevation_edit));
+
+        let mut sun_azimuth_edit = TheTextLineEdit::new(TheId::named("Sun Azimuth"));
+        sun_azimuth_edit.set_text(project.sky.sun_azimuth.to_string());
+        sun_azimuth_edit.set_range(TheValue::RangeF32(0.0..=360.0));
+        sun_azimuth_edit.set_status_text("The azimuth of the sun.");
+        text_layout.add_pair("Sun Azimuth".to_string(), Box::new(sun_azimuth_edit));
+
+        let mut turbidity_edit = TheTextLineEdit::new(TheId::named("Turbidity"));
+        turbidity_edit.set_text(project.sky.turbidity.to_string());
+        turbidity_edit.set_range(TheValue::RangeF32(2.0..=10.0));
+        turbidity_edit.set_status_text("The haziness of the sky, from clear to hazy.");
+        text_layout.add_pair("Turbidity".to_string(), Box::new(turbidity_edit));
+
         center_canvas.set_layout(text_layout);
 
         // let mut center_color_canvas = TheCanvas::default();
@@ -103,6 +334,95 @@ impl Panel {
         canvas
     }
 
+    /// Shows the settings of the loaded project in the widgets.
+    pub fn load_from_project(&mut self, ui: &mut TheUI, _ctx: &mut TheContext, project: &Project) {
+        let settings = &project.settings;
+        let environment = project.environment.as_ref();
+
+        let texts = [
+            ("Iso Value", settings.iso_value.to_string()),
+            ("Pixel Threshold", settings.pixel_threshold.to_string()),
+            ("Max Distance", settings.t_max.to_string()),
+            ("Max Steps", settings.max_steps.to_string()),
+            ("Step Size", settings.step_size.to_string()),
+            ("Relaxation", settings.relaxation.to_string()),
+            ("AA", settings.aa.to_string()),
+            (
+                "Adaptive Threshold",
+                settings.adaptive_threshold.to_string(),
+            ),
+            ("Max Depth", settings.max_depth.to_string()),
+            ("Max Samples", settings.max_samples.to_string()),
+            ("Exposure", settings.exposure.to_string()),
+            ("AO Steps", settings.ao_steps.to_string()),
+            ("AO Strength", settings.ao_strength.to_string()),
+            (
+                "Env Rotation",
+                environment.map_or(0.0, |e| e.rotation).to_string(),
+            ),
+            (
+                "Env Intensity",
+                environment.map_or(1.0, |e| e.intensity).to_string(),
+            ),
+            ("Sun Elevation", project.sky.sun_elevation.to_string()),
+            ("Sun Azimuth", project.sky.sun_azimuth.to_string()),
+            ("Turbidity", project.sky.turbidity.to_string()),
+        ];
+        for (name, text) in texts {
+            if let Some(widget) = ui.canvas.get_widget(Some(&name.to_string()), None) {
+                widget.set_value(TheValue::Text(text));
+            }
+        }
+
+        let checks = [
+            ("Shadows", settings.shadows),
+            ("Refine Hits", settings.refine),
+            ("Packets", settings.packets),
+            ("Distance Cache", settings.distance_cache),
+            ("Adaptive", settings.adaptive),
+            ("Sky", project.sky.enabled),
+        ];
+        for (name, checked) in checks {
+            if let Some(widget) = ui.canvas.get_widget(Some(&name.to_string()), None) {
+                widget.set_state(if checked {
+                    TheWidgetState::Selected
+                } else {
+                    TheWidgetState::None
+                });
+            }
+        }
+
+        let dropdowns = [
+            (
+                "March Method",
+                option_index(&MarchMethod::all(), &settings.method),
+            ),
+            (
+                "Precision",
+                option_index(&Precision::all(), &settings.precision),
+            ),
+            (
+                "Anti-Aliasing",
+                option_index(&AntiAliasing::all(), &settings.antialiasing),
+            ),
+            (
+                "Sampling",
+                option_index(&SamplePattern::all(), &settings.sampling),
+            ),
+            (
+                "Tone Mapping",
+                option_index(&ToneMapping::all(), &settings.tone_mapping),
+            ),
+        ];
+        for (name, index) in dropdowns {
+            if let Some(widget) = ui.canvas.get_widget(Some(&name.to_string()), None) {
+                if let Some(dropdown) = widget.as_drop_down_menu() {
+                    dropdown.set_selected_index(index);
+                }
+            }
+        }
+    }
+
     pub fn handle_event(
         &mut self,
         event: &TheEvent,
@@ -113,8 +433,203 @@ impl Panel {
     ) -> bool {
         let mut redraw = false;
         match event {
+            TheEvent::StateChanged(id, state) => {
+                if id.name == "Shadows" {
+                    project.settings.shadows = *state == TheWidgetState::Selected;
+                    redraw = true;
+                } else if id.name == "Packets" {
+                    project.settings.packets = *state == TheWidgetState::Selected;
+                    redraw = true;
+                } else if id.name == "Distance Cache" {
+                    project.settings.distance_cache = *state == TheWidgetState::Selected;
+                    redraw = true;
+                } else if id.name == "Adaptive" {
+                    project.settings.adaptive = *state == TheWidgetState::Selected;
+                    redraw = true;
+                } else if id.name == "Refine Hits" {
+                    project.settings.refine = *state == TheWidgetState::Selected;
+                    redraw = true;
+                } else if id.name == "Sky" {
+                    project.sky.enabled = *state == TheWidgetState::Selected;
+                    redraw = true;
+                } else if id.name == "Export Passes" {
+                    ctx.ui.save_file_requester(
+                        TheId::named_with_id(id.name.as_str(), Uuid::new_v4()),
+                        "Export Passes".into(),
+                        TheFileExtension::new(
+                            "Images".into(),
+                            vec!["png".to_string(), "exr".to_string(), "pfm".to_string()],
+                        ),
+                    );
+                    ctx.ui
+                        .set_widget_state("Export Passes".to_string(), TheWidgetState::None);
+                    ctx.ui.clear_hover();
+                    redraw = true;
+                } else if id.name == "Load HDR" {
+                    ctx.ui.open_file_requester(
+                        TheId::named_with_id(id.name.as_str(), Uuid::new_v4()),
+                        "Load HDR".into(),
+                        TheFileExtension::new("HDR".into(), vec!["hdr".to_string()]),
+                    );
+                    ctx.ui
+                        .set_widget_state("Load HDR".to_string(), TheWidgetState::None);
+                    ctx.ui.clear_hover();
+                    redraw = true;
+                }
+            }
+            TheEvent::FileRequesterResult(id, paths) => {
+                if id.name == "Load HDR" {
+                    for p in paths {
+                        if let Ok(data) = std::fs::read(p) {
+                            if decode_hdr(&data).is_some() {
+                                let name = p
+                                    .file_stem()
+                                    .map(|s| s.to_string_lossy().to_string())
+                                    .unwrap_or_default();
+                                let mut environment = Environment::new(name, p.clone());
+                                if let Some(old) = &project.environment {
+                                    environment.rotation = old.rotation;
+                                    environment.intensity = old.intensity;
+                                }
+                                project.environment = Some(environment);
+                                redraw = true;
+                            }
+                        }
+                    }
+                }
+            }
+            TheEvent::IndexChanged(id, index) => {
+                if id.name == "March Method" {
+                    if let Some(method) = MarchMethod::all().get(*index) {
+                        project.settings.method = *method;
+                        redraw = true;
+                    }
+                } else if id.name == "Anti-Aliasing" {
+                    if let Some(antialiasing) = AntiAliasing::all().get(*index) {
+                        project.settings.antialiasing = *antialiasing;
+                        redraw = true;
+                    }
+                } else if id.name == "Sampling" {
+                    if let Some(pattern) = SamplePattern::all().get(*index) {
+                        project.settings.sampling = *pattern;
+                        redraw = true;
+                    }
+                } else if id.name == "Tone Mapping" {
+                    if let Some(tone_mapping) = ToneMapping::all().get(*index) {
+                        project.settings.tone_mapping = *tone_mapping;
+                        redraw = true;
+                    }
+                } else if id.name == "Precision" {
+                    if let Some(precision) = Precision::all().get(*index) {
+                        project.settings.precision = *precision;
+                        redraw = true;
+                    }
+                }
+            }
+            TheEvent::ValueChanged(id, value) => {
+                if id.name == "Relaxation" {
+                    if let Some(relaxation) = value.to_f32() {
+                        project.settings.relaxation = relaxation as f64;
+                        redraw = true;
+                    }
+                } else if id.name == "Iso Value" {
+                    if let Some(iso_value) = value.to_f32() {
+                        project.settings.iso_value = iso_value as f64;
+                        redraw = true;
+                    }
+                } else if id.name == "Adaptive Threshold" {
+                    if let Some(threshold) = value.to_f32() {
+                        project.settings.adaptive_threshold = threshold as f64;
+                        redraw = true;
+                    }
+                } else if id.name == "Max Depth" {
+                    if let Some(max_depth) = value.to_i32() {
+                        project.settings.max_depth = max_depth as usize;
+                        redraw = true;
+                    }
+                } else if id.name == "Max Samples" {
+                    if let Some(samples) = value.to_i32() {
+                        project.settings.max_samples = samples.max(1) as u32;
+                        redraw = true;
+                    }
+                } else if id.name == "Exposure" {
+                    if let Some(exposure) = value.to_f32() {
+                        project.settings.exposure = exposure as f64;
+                        redraw = true;
+                    }
+                } else if id.name == "Pixel Threshold" {
+                    if let Some(pixel_threshold) = value.to_f32() {
+                        project.settings.pixel_threshold = pixel_threshold as f64;
+                        redraw = true;
+                    }
+                } else if id.name == "Max Distance" {
+                    if let Some(t_max) = value.to_f32() {
+                        project.settings.t_max = t_max as f64;
+                        redraw = true;
+                    }
+                } else if id.name == "Max Steps" {
+                    if let Some(steps) = value.to_i32() {
+                        project.settings.max_steps = steps.max(1) as usize;
+                        redraw = true;
+                    }
+                } else if id.name == "Step Size" {
+                    if let Some(step_size) = value.to_f32() {
+                        project.settings.step_size = step_size as f64;
+                        redraw = true;
+                    }
+                } else if id.name == "AA" {
+                    if let Some(aa) = value.to_i32() {
+                        project.settings.aa = aa.max(1) as usize;
+                        redraw = true;
+                    }
+                } else if id.name == "AO Steps" {
+                    if let Some(steps) = value.to_i32() {
+                        project.settings.ao_steps = steps.max(0) as usize;
+                        redraw = true;
+                    }
+                } else if id.name == "AO Strength" {
+                    if let Some(strength) = value.to_f32() {
+                        project.settings.ao_strength = strength as f64;
+                        redraw = true;
+                    }
+                } else if id.name == "Env Rotation" {
+                    if let (Some(rotation), Some(environment)) =
+                        (value.to_f32(), &mut project.environment)
+                    {
+                        environment.rotation = rotation as f64;
+                        redraw = true;
+                    }
+                } else if id.name == "Sun Elevation" {
+                    if let Some(elevation) = value.to_f32() {
+                        project.sky.sun_elevation = elevation as f64;
+                        redraw = true;
+                    }
+                } else if id.name == "Sun Azimuth" {
+                    if let Some(azimuth) = value.to_f32() {
+                        project.sky.sun_azimuth = azimuth as f64;
+                        redraw = true;
+                    }
+                } else if id.name == "Turbidity" {
+                    if let Some(turbidity) = value.to_f32() {
+                        project.sky.turbidity = turbidity as f64;
+                        redraw = true;
+                    }
+                } else if id.name == "Env Intensity" {
+                    if let (Some(intensity), Some(environment)) =
+                        (value.to_f32(), &mut project.environment)
+                    {
+                        environment.intensity = intensity as f64;
+                        redraw = true;
+                    }
+                }
+            }
             _ => {}
         }
         redraw
     }
 }
+
+/// The index of the value in the dropdown options.
+fn option_index<T: PartialEq>(options: &[T], value: &T) -> i32 {
+    options.iter().position(|o| o == value).unwrap_or(0) as i32
+}
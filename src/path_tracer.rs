@@ -0,0 +1,239 @@
+use crate::prelude::*;
+use rayon::prelude::*;
+use std::f64::consts::FRAC_1_PI;
+use theframework::prelude::*;
+
+/// Progressive Monte Carlo path tracer over the distance field. Every call to render adds one
+/// sample per pixel to the accumulation buffer, which is reset by [`PathTracer::reset`] and when
+/// the buffer size changes.
+pub struct PathTracer {
+    accum: Vec<Vec3d>,
+    samples: u32,
+
+    width: usize,
+    height: usize,
+
+    pub max_bounces: usize,
+}
+
+#[allow(clippy::new_without_default)]
+impl PathTracer {
+    pub fn new() -> Self {
+        Self {
+            accum: Vec::new(),
+            samples: 0,
+
+            width: 0,
+            height: 0,
+
+            max_bounces: 6,
+        }
+    }
+
+    /// Discards the accumulated samples, called whenever the project changes.
+    pub fn reset(&mut self) {
+        self.samples = 0;
+    }
+
+    /// The number of accumulated samples per pixel.
+    pub fn samples(&self) -> u32 {
+        self.samples
+    }
+
+    /// Adds one sample per pixel and writes the current estimate into the buffer. The tracer has
+    /// to be prepared for the project.
+    pub fn render(&mut self, buffer: &mut TheRGBABuffer, project: &Project, tracer: &Tracer) {
+        let width = buffer.dim().width as usize;
+        let height = buffer.dim().height as usize;
+
+        if width != self.width || height != self.height || self.samples == 0 {
+            self.width = width;
+            self.height = height;
+            self.accum = vec![Vec3d::zero(); width * height];
+            self.samples = 0;
+        }
+
+        if width == 0 || height == 0 {
+            return;
+        }
+
+        let width_f = width as f64;
+        let height_f = height as f64;
+
+        let camera = &project.camera;
+        let camera_mode = project.camera_mode;
+        let settings = &project.settings;
+
+        let sample = self.samples;
+        let max_bounces = self.max_bounces;
+        let scale = 1.0 / (sample + 1) as f64;
+
+        self.accum
+            .par_chunks_exact_mut(width)
+            .zip(buffer.pixels_mut().par_rchunks_exact_mut(width * 4))
+            .enumerate()
+            .for_each(|(j, (accum_line, line))| {
+                for (i, (acc, pixel)) in accum_line
+                    .iter_mut()
+                    .zip(line.chunks_exact_mut(4))
+                    .enumerate()
+                {
+                    let mut rng = Pcg32::new((((j * width + i) as u64) << 32) | sample as u64);
+
+                    let ray = camera.create_camera_ray(
+                        camera_mode,
+                        vec2d(i as f64 / width_f, j as f64 / height_f),
+                        vec2d(width_f, height_f),
+                        rng.next_vec2d() - vec2d(0.5, 0.5),
+                    );
+
+                    let c = Self::radiance(tracer, ray, &mut rng, max_bounces);
+                    if c.x.is_finite() && c.y.is_finite() && c.z.is_finite() {
+                        *acc += c;
+                    }
+
+                    let [r, g, b] = encode_pass(*acc * scale, RenderPass::Shaded, settings);
+                    pixel.copy_from_slice(&[r, g, b, 255]);
+                }
+            });
+
+        self.samples += 1;
+    }
+
+    /// Estimates the incoming radiance along the ray.
+    fn radiance(tracer: &Tracer, mut ray: Ray, rng: &mut Pcg32, max_bounces: usize) -> Vec3d {
+        let t_max = tracer.settings().t_max;
+
+        let mut radiance = Vec3d::zero();
+        let mut throughput = Vec3d::one();
+        // The last bounce was a mirror reflection or a refraction, which next event estimation
+        // does not cover
+        let mut specular = true;
+
+        for bounce in 0..max_bounces {
+            let t = match tracer.intersect(&ray, t_max) {
+                Some(t) => t,
+                None => {
+                    // Scattered rays skip the sun disk which next event estimation covers
+                    let background = if specular {
+                        tracer.background(ray.d)
+                    } else {
+                        tracer.indirect_background(ray.d)
+                    };
+                    radiance += throughput * background;
+                    break;
+                }
+            };
+
+            let p = ray.at(t);
+            let step = tracer.hit_threshold(&ray, t);
+            let n = tracer.normal(p, step);
+            let v = -ray.d;
+            let n_dot_v = dot(n, v).max(0.0001);
+
+            let material = tracer.material(&tracer.map(p));
+            let (f0, diffuse) = specular_diffuse(&material);
+            let alpha = ggx_alpha(&material);
+
+            radiance += throughput * material.emission;
+
+            let bias = tracer.surface_bias(step);
+            let origin = p + n * bias;
+
+            // The transmissive part is a dielectric which reflects by the Fresnel term of its
+            // ior, refracted rays pass through the solid and continue where they leave it
+            if material.transmission > 0.0 && rng.next_f64() < material.transmission {
+                let ior = material.ior;
+                let f0 = ((ior - 1.0) / (ior + 1.0)).powi(2);
+                let fresnel = fresnel_schlick(vec3d(f0, f0, f0), n_dot_v).x;
+
+                specular = true;
+                ray = match refract_dir(ray.d, n, 1.0 / ior) {
+                    Some(dir) if rng.next_f64() >= fresnel => {
+                        let inside = Ray::new(p - n * bias, dir);
+                        let Some(out) = Self::transmit(tracer, inside, ior, max_bounces) else {
+                            break;
+                        };
+                        throughput = throughput * material.color;
+                        out
+                    }
+                    _ => Ray::new(origin, reflect_dir(ray.d, n)),
+                };
+                continue;
+            }
+            specular = false;
+
+            // Next event estimation for the analytic lights
+            for light in tracer.lights() {
+                let (l, distance) = light.direction_from(p);
+                let n_dot_l = dot(n, l);
+                if n_dot_l <= 0.0 {
+                    continue;
+                }
+
+                let shadow_ray = Ray::new(origin, l);
+                if tracer.intersect(&shadow_ray, distance.min(t_max)).is_some() {
+                    continue;
+                }
+
+                let brdf = diffuse * FRAC_1_PI + ggx_specular(n, v, l, f0, alpha);
+                radiance += throughput * brdf * light.radiance(distance) * n_dot_l;
+            }
+
+            // Pick the specular or the diffuse lobe for the next bounce
+            let spec_weight = luminance(f0);
+            let p_spec =
+                (spec_weight / (spec_weight + luminance(diffuse)).max(0.0001)).clamp(0.1, 0.9);
+
+            let direction = if rng.next_f64() < p_spec {
+                let h = sample_ggx(n, alpha, rng.next_vec2d());
+                let l = reflect_dir(-v, h);
+                let n_dot_l = dot(n, l);
+                if n_dot_l <= 0.0 {
+                    break;
+                }
+
+                let v_dot_h = dot(v, h).max(0.0);
+                let n_dot_h = dot(n, h).max(0.0001);
+                let f = fresnel_schlick(f0, v_dot_h);
+                let g = smith_g1(n_dot_v, alpha) * smith_g1(n_dot_l, alpha);
+
+                throughput = throughput * f * (g * v_dot_h / (n_dot_v * n_dot_h * p_spec));
+                l
+            } else {
+                throughput = throughput * diffuse * (1.0 / (1.0 - p_spec));
+                cosine_hemisphere(n, rng.next_vec2d())
+            };
+
+            // Russian roulette
+            if bounce >= 3 {
+                let q = throughput
+                    .x
+                    .max(throughput.y.max(throughput.z))
+                    .clamp(0.05, 0.95);
+                if rng.next_f64() > q {
+                    break;
+                }
+                throughput = throughput * (1.0 / q);
+            }
+
+            ray = Ray::new(origin, direction);
+        }
+
+        radiance
+    }
+
+    /// Follows a refracted ray through the inside of a solid and returns the ray leaving it,
+    /// rays which are totally reflected at the surface stay inside.
+    fn transmit(tracer: &Tracer, mut ray: Ray, ior: f64, max_bounces: usize) -> Option<Ray> {
+        let bias = tracer.surface_bias(tracer.settings().iso_value);
+        for _ in 0..max_bounces {
+            let (p, normal) = tracer.exit(&ray)?;
+            match refract_dir(ray.d, -normal, ior) {
+                Some(dir) => return Some(Ray::new(p + normal * bias, dir)),
+                None => ray = Ray::new(p - normal * bias, reflect_dir(ray.d, normal)),
+            }
+        }
+        None
+    }
+}
@@ -28,11 +28,13 @@ impl PointTracer {
         let fov = 70.0;
         let camera_mode = CameraMode::Pinhole;
 
-        let aa = 2;
+        let settings = &project.settings;
+
+        let aa = settings.aa.max(1);
         let aa_f = aa as f64;
 
         let pixels = buffer.pixels_mut();
-        let iso_value = 0.0001_f64;
+        let iso_value = settings.iso_value;
 
         pixels
             .par_rchunks_exact_mut(width * 4)
@@ -71,16 +73,16 @@ impl PointTracer {
                             let mut color = vec4d(0.0, 0.0, 0.0, 1.0);
 
                             let mut t = iso_value;
-                            let t_max = 10.0;
+                            let t_max = settings.t_max;
 
                             let mut hit = false;
 
-                            for _ in 0..100 {
+                            for _ in 0..settings.max_steps {
                                 let p = ray.at(t);
 
                                 let d = self.distance(p);
 
-                                t += d;
+                                t += d * settings.step_size;
 
                                 if d < iso_value {
                                     hit = true;
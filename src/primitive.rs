@@ -0,0 +1,89 @@
+use crate::prelude::*;
+
+/// The signed distance primitives, all centered at the local origin.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy, Debug)]
+pub enum Primitive {
+    Sphere {
+        radius: f64,
+    },
+    /// A box with the given half extents and rounded edges.
+    Box {
+        size: Vec3d,
+        rounding: f64,
+    },
+    /// A torus lying in the xz plane.
+    Torus {
+        radius: f64,
+        thickness: f64,
+    },
+    /// A capsule along the y axis starting at the origin.
+    Capsule {
+        height: f64,
+        radius: f64,
+    },
+}
+
+impl Default for Primitive {
+    fn default() -> Self {
+        Primitive::Sphere { radius: 1.0 }
+    }
+}
+
+impl Primitive {
+    /// Returns the signed distance of the local space point p to the primitive.
+    pub fn distance(&self, p: Vec3d) -> f64 {
+        self.eval(&Scalar3::constant(p))
+    }
+
+    /// Returns the signed distance of the local space point p in any scalar type, which gives the
+    /// distances of packets, the gradient for dual numbers and the range over a box for
+    /// intervals.
+    #[inline(always)]
+    pub fn eval<T: Scalar>(&self, p: &Scalar3<T>) -> T {
+        let c = T::constant;
+        match *self {
+            Primitive::Sphere { radius } => p.length() - c(radius),
+            Primitive::Box { size, rounding } => {
+                let a = p.abs();
+                let q = Scalar3::new(
+                    a.x - c(size.x) + c(rounding),
+                    a.y - c(size.y) + c(rounding),
+                    a.z - c(size.z) + c(rounding),
+                );
+                let zero = c(0.0);
+                let outside = Scalar3::new(q.x.max(zero), q.y.max(zero), q.z.max(zero));
+                outside.length() + q.x.max(q.y.max(q.z)).min(zero) - c(rounding)
+            }
+            Primitive::Torus { radius, thickness } => {
+                let qx = (p.x.sqr() + p.z.sqr()).sqrt() - c(radius);
+                (qx.sqr() + p.y.sqr()).sqrt() - c(thickness)
+            }
+            Primitive::Capsule { height, radius } => {
+                // y - clamp(y, 0, height) written with monotonic terms, which keeps the
+                // interval of the capsule tight
+                let zero = c(0.0);
+                let qy = p.y.min(zero) + (p.y - c(height)).max(zero);
+                Scalar3::new(p.x, qy, p.z).length() - c(radius)
+            }
+        }
+    }
+
+    /// Returns the local space bounds of the primitive.
+    pub fn bounds(&self) -> Aabb {
+        match *self {
+            Primitive::Sphere { radius } => Aabb::new(
+                vec3d(-radius, -radius, -radius),
+                vec3d(radius, radius, radius),
+            ),
+            Primitive::Box { size, .. } => Aabb::new(Vec3d::zero() - size, size),
+            Primitive::Torus { radius, thickness } => {
+                let r = radius + thickness;
+                Aabb::new(vec3d(-r, -thickness, -r), vec3d(r, thickness, r))
+            }
+            Primitive::Capsule { height, radius } => Aabb::new(
+                vec3d(-radius, -radius, -radius),
+                vec3d(radius, height + radius, radius),
+            ),
+        }
+    }
+}
@@ -6,6 +6,22 @@ pub struct Project {
     pub id: Uuid,
 
     pub objects: Vec<Object>,
+
+    #[serde(default)]
+    pub lights: Vec<Light>,
+
+    #[serde(default)]
+    pub environment: Option<Environment>,
+    #[serde(default)]
+    pub sky: Sky,
+
+    #[serde(default)]
+    pub settings: RenderSettings,
+
+    #[serde(default)]
+    pub camera: Camera,
+    #[serde(default)]
+    pub camera_mode: CameraMode,
 }
 
 impl Default for Project {
@@ -20,7 +36,20 @@ impl Project {
             name: String::new(),
             id: Uuid::new_v4(),
 
-            objects: Vec::new(),
+            objects: vec![Object::default()],
+
+            lights: vec![Light::default()],
+
+            environment: None,
+            sky: Sky {
+                enabled: true,
+                ..Sky::default()
+            },
+
+            settings: RenderSettings::default(),
+
+            camera: Camera::default(),
+            camera_mode: CameraMode::default(),
         }
     }
 
@@ -1,3 +1,4 @@
+use crate::editor::TRACER;
 use crate::prelude::*;
 
 pub struct RenderView {}
@@ -22,6 +23,24 @@ impl RenderView {
     ) -> TheCanvas {
         let mut canvas = TheCanvas::new();
 
+        // Toolbar
+        let mut toolbar_canvas = TheCanvas::default();
+        let mut toolbar_hlayout = TheHLayout::new(TheId::empty());
+        toolbar_hlayout.limiter_mut().set_max_height(25);
+        toolbar_hlayout.set_margin(vec4i(10, 2, 5, 3));
+        toolbar_canvas.set_widget(TheTraybar::new(TheId::empty()));
+        toolbar_hlayout.set_background_color(None);
+
+        let mut pass_dropdown = TheDropdownMenu::new(TheId::named("Render Pass"));
+        for pass in RenderPass::all() {
+            pass_dropdown.add_option(pass.name().to_string());
+        }
+        pass_dropdown.set_status_text("The render pass shown in the render view.");
+        toolbar_hlayout.add_widget(Box::new(pass_dropdown));
+
+        toolbar_canvas.set_layout(toolbar_hlayout);
+        canvas.set_top(toolbar_canvas);
+
         let render_view = TheRenderView::new(TheId::named("Render View"));
         canvas.set_widget(render_view);
         canvas
@@ -37,6 +56,14 @@ impl RenderView {
     ) -> bool {
         let mut redraw = false;
         match event {
+            TheEvent::IndexChanged(id, index) => {
+                if id.name == "Render Pass" {
+                    if let Some(pass) = RenderPass::all().get(*index) {
+                        TRACER.lock().unwrap().pass = *pass;
+                        redraw = true;
+                    }
+                }
+            }
             _ => {}
         }
         redraw
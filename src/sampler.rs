@@ -0,0 +1,112 @@
+use crate::prelude::*;
+use std::f64::consts::PI;
+
+/// A small and fast PCG random number generator, seeded per pixel and sample.
+#[derive(Clone, Debug)]
+pub struct Pcg32 {
+    state: u64,
+}
+
+impl Pcg32 {
+    pub fn new(seed: u64) -> Self {
+        // Scramble the seed with splitmix64 so that neighboring seeds are uncorrelated
+        let mut z = seed.wrapping_add(0x9E3779B97F4A7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+
+        let mut rng = Self {
+            state: z ^ (z >> 31),
+        };
+        rng.next_u32();
+        rng
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.state = old
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        let rot = (old >> 59) as u32;
+        xorshifted.rotate_right(rot)
+    }
+
+    /// Returns a random number in [0, 1).
+    pub fn next_f64(&mut self) -> f64 {
+        self.next_u32() as f64 / 4294967296.0
+    }
+
+    /// Returns a random point in [0, 1)².
+    pub fn next_vec2d(&mut self) -> Vec2d {
+        vec2d(self.next_f64(), self.next_f64())
+    }
+}
+
+/// Returns the offset in [-0.5, 0.5)² of the sample of the cell (m, n) of the aa x aa grid of
+/// the pixel (x, y).
+pub fn pixel_sample(
+    pattern: SamplePattern,
+    (m, n): (usize, usize),
+    aa: usize,
+    (x, y): (usize, usize),
+) -> Vec2d {
+    let aa_f = aa as f64;
+    let index = n * aa + m;
+
+    let u = match pattern {
+        SamplePattern::Grid => vec2d((m as f64 + 0.5) / aa_f, (n as f64 + 0.5) / aa_f),
+        SamplePattern::Stratified => {
+            let mut rng = Pcg32::new(((x as u64) << 40) ^ ((y as u64) << 20) ^ index as u64);
+            let r = rng.next_vec2d();
+            vec2d((m as f64 + r.x) / aa_f, (n as f64 + r.y) / aa_f)
+        }
+        SamplePattern::BlueNoise => {
+            // The R2 sequence (Roberts 2018) shifted per pixel by interleaved gradient noise
+            // (Jimenez 2014)
+            const R2: (f64, f64) = (0.7548776662466927, 0.5698402909980532);
+            let (x, y) = (x as f64, y as f64);
+            let shift = vec2d(
+                gradient_noise(x, y),
+                gradient_noise(y + 5.588238, x + 5.588238),
+            );
+            let i = index as f64;
+            vec2d(
+                (0.5 + i * R2.0 + shift.x).fract(),
+                (0.5 + i * R2.1 + shift.y).fract(),
+            )
+        }
+    };
+
+    u - vec2d(0.5, 0.5)
+}
+
+/// Interleaved gradient noise, a cheap noise in [0, 1) with most of its energy in high
+/// frequencies.
+fn gradient_noise(x: f64, y: f64) -> f64 {
+    (52.9829189 * (0.06711056 * x + 0.00583715 * y).fract()).fract()
+}
+
+/// Builds an orthonormal basis around n. Based on Duff et al. 2017.
+pub fn orthonormal_basis(n: Vec3d) -> (Vec3d, Vec3d) {
+    let sign = 1.0_f64.copysign(n.z);
+    let a = -1.0 / (sign + n.z);
+    let b = n.x * n.y * a;
+    (
+        vec3d(1.0 + sign * n.x * n.x * a, sign * b, -sign * n.x),
+        vec3d(b, sign + n.y * n.y * a, -n.y),
+    )
+}
+
+/// Transforms the tangent space vector v into the frame around n.
+pub fn to_world_frame(v: Vec3d, n: Vec3d) -> Vec3d {
+    let (t, b) = orthonormal_basis(n);
+    t * v.x + b * v.y + n * v.z
+}
+
+/// Returns a cosine weighted direction in the hemisphere around n.
+pub fn cosine_hemisphere(n: Vec3d, u: Vec2d) -> Vec3d {
+    let r = u.x.sqrt();
+    let phi = 2.0 * PI * u.y;
+    let v = vec3d(r * phi.cos(), r * phi.sin(), (1.0 - u.x).max(0.0).sqrt());
+    to_world_frame(v, n)
+}
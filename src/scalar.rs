@@ -0,0 +1,228 @@
+use crate::prelude::*;
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+/// The number types the distance functions are generic over, f64 for rendering, f32 for
+/// faster previews, dual numbers for gradients, intervals for bounds and packets of lanes.
+pub trait Scalar:
+    Copy
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + Neg<Output = Self>
+{
+    /// Converts a constant of the scene.
+    fn constant(v: f64) -> Self;
+
+    fn abs(self) -> Self;
+    fn sqrt(self) -> Self;
+    fn min(self, other: Self) -> Self;
+    fn max(self, other: Self) -> Self;
+    fn clamp(self, min: f64, max: f64) -> Self;
+
+    fn powi(self, n: i32) -> Self;
+    fn sin(self) -> Self;
+    fn cos(self) -> Self;
+    fn exp(self) -> Self;
+
+    #[inline(always)]
+    fn sqr(self) -> Self {
+        self * self
+    }
+}
+
+/// Scalars with a single value, which the CSG operations compare to track the nearest object.
+pub trait Real: Scalar {
+    /// The largest value, stands for an empty scene.
+    fn empty() -> Self;
+    fn value(self) -> f64;
+}
+
+impl Scalar for f64 {
+    #[inline(always)]
+    fn constant(v: f64) -> Self {
+        v
+    }
+
+    #[inline(always)]
+    fn abs(self) -> Self {
+        f64::abs(self)
+    }
+
+    #[inline(always)]
+    fn sqrt(self) -> Self {
+        f64::sqrt(self)
+    }
+
+    #[inline(always)]
+    fn min(self, other: Self) -> Self {
+        f64::min(self, other)
+    }
+
+    #[inline(always)]
+    fn max(self, other: Self) -> Self {
+        f64::max(self, other)
+    }
+
+    #[inline(always)]
+    fn clamp(self, min: f64, max: f64) -> Self {
+        f64::clamp(self, min, max)
+    }
+
+    #[inline(always)]
+    fn powi(self, n: i32) -> Self {
+        f64::powi(self, n)
+    }
+
+    #[inline(always)]
+    fn sin(self) -> Self {
+        f64::sin(self)
+    }
+
+    #[inline(always)]
+    fn cos(self) -> Self {
+        f64::cos(self)
+    }
+
+    #[inline(always)]
+    fn exp(self) -> Self {
+        f64::exp(self)
+    }
+}
+
+impl Real for f64 {
+    #[inline(always)]
+    fn empty() -> Self {
+        f64::MAX
+    }
+
+    #[inline(always)]
+    fn value(self) -> f64 {
+        self
+    }
+}
+
+impl Scalar for f32 {
+    #[inline(always)]
+    fn constant(v: f64) -> Self {
+        v as f32
+    }
+
+    #[inline(always)]
+    fn abs(self) -> Self {
+        f32::abs(self)
+    }
+
+    #[inline(always)]
+    fn sqrt(self) -> Self {
+        f32::sqrt(self)
+    }
+
+    #[inline(always)]
+    fn min(self, other: Self) -> Self {
+        f32::min(self, other)
+    }
+
+    #[inline(always)]
+    fn max(self, other: Self) -> Self {
+        f32::max(self, other)
+    }
+
+    #[inline(always)]
+    fn clamp(self, min: f64, max: f64) -> Self {
+        f32::clamp(self, min as f32, max as f32)
+    }
+
+    #[inline(always)]
+    fn powi(self, n: i32) -> Self {
+        f32::powi(self, n)
+    }
+
+    #[inline(always)]
+    fn sin(self) -> Self {
+        f32::sin(self)
+    }
+
+    #[inline(always)]
+    fn cos(self) -> Self {
+        f32::cos(self)
+    }
+
+    #[inline(always)]
+    fn exp(self) -> Self {
+        f32::exp(self)
+    }
+}
+
+impl Real for f32 {
+    #[inline(always)]
+    fn empty() -> Self {
+        f32::MAX
+    }
+
+    #[inline(always)]
+    fn value(self) -> f64 {
+        self as f64
+    }
+}
+
+/// A point of any scalar type.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct Scalar3<T: Scalar> {
+    pub x: T,
+    pub y: T,
+    pub z: T,
+}
+
+impl<T: Scalar> Scalar3<T> {
+    pub fn new(x: T, y: T, z: T) -> Self {
+        Self { x, y, z }
+    }
+
+    /// Converts a point of the scene.
+    pub fn constant(p: Vec3d) -> Self {
+        Self::new(T::constant(p.x), T::constant(p.y), T::constant(p.z))
+    }
+
+    pub fn translate(&self, v: Vec3d) -> Self {
+        Self::new(
+            self.x + T::constant(v.x),
+            self.y + T::constant(v.y),
+            self.z + T::constant(v.z),
+        )
+    }
+
+    pub fn scale(&self, s: f64) -> Self {
+        let s = T::constant(s);
+        Self::new(self.x * s, self.y * s, self.z * s)
+    }
+
+    pub fn abs(&self) -> Self {
+        Self::new(self.x.abs(), self.y.abs(), self.z.abs())
+    }
+
+    pub fn length(&self) -> T {
+        (self.x.sqr() + self.y.sqr() + self.z.sqr()).sqrt()
+    }
+
+    /// Rotates around the x axis by the angle in radians, see [`rotate_x`].
+    pub fn rotate_x(&self, angle: f64) -> Self {
+        let (s, c) = angle.sin_cos();
+        let (s, c) = (T::constant(s), T::constant(c));
+        Self::new(self.x, self.y * c - self.z * s, self.y * s + self.z * c)
+    }
+
+    /// Rotates around the y axis by the angle in radians, see [`rotate_y`].
+    pub fn rotate_y(&self, angle: f64) -> Self {
+        let (s, c) = angle.sin_cos();
+        let (s, c) = (T::constant(s), T::constant(c));
+        Self::new(self.x * c + self.z * s, self.y, self.z * c - self.x * s)
+    }
+
+    /// Rotates around the z axis by the angle in radians, see [`rotate_z`].
+    pub fn rotate_z(&self, angle: f64) -> Self {
+        let (s, c) = angle.sin_cos();
+        let (s, c) = (T::constant(s), T::constant(c));
+        Self::new(self.x * c - self.y * s, self.x * s + self.y * c, self.z)
+    }
+}
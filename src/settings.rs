@@ -0,0 +1,249 @@
+use crate::prelude::*;
+
+/// The sphere tracing loop used to find the surface along a ray.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy, Debug, Default)]
+pub enum MarchMethod {
+    /// Steps by the distance to the surface.
+    #[default]
+    Naive,
+    /// Over-relaxed sphere tracing (Keinert et al. 2014), falls back to naive steps when the
+    /// unbounding spheres of two steps do not overlap.
+    OverRelaxed,
+}
+
+impl MarchMethod {
+    pub fn all() -> [MarchMethod; 2] {
+        [MarchMethod::Naive, MarchMethod::OverRelaxed]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            MarchMethod::Naive => "naive",
+            MarchMethod::OverRelaxed => "over-relaxed",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<MarchMethod> {
+        Self::all().into_iter().find(|method| method.name() == name)
+    }
+}
+
+/// How pixels are anti-aliased.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy, Debug, Default)]
+pub enum AntiAliasing {
+    /// aa x aa rays per pixel.
+    #[default]
+    Supersampling,
+    /// A single ray per pixel, silhouettes are blended by the coverage of the pixel cone.
+    Cone,
+}
+
+impl AntiAliasing {
+    pub fn all() -> [AntiAliasing; 2] {
+        [AntiAliasing::Supersampling, AntiAliasing::Cone]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            AntiAliasing::Supersampling => "supersampling",
+            AntiAliasing::Cone => "cone",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<AntiAliasing> {
+        Self::all().into_iter().find(|aa| aa.name() == name)
+    }
+}
+
+/// Where the supersamples are placed inside a pixel.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy, Debug, Default)]
+pub enum SamplePattern {
+    /// The centers of a regular aa x aa grid.
+    #[default]
+    Grid,
+    /// A random point in each cell of the aa x aa grid.
+    Stratified,
+    /// A low-discrepancy sequence shifted per pixel by blue noise, errors are spread evenly
+    /// over neighboring pixels.
+    BlueNoise,
+}
+
+impl SamplePattern {
+    pub fn all() -> [SamplePattern; 3] {
+        [
+            SamplePattern::Grid,
+            SamplePattern::Stratified,
+            SamplePattern::BlueNoise,
+        ]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SamplePattern::Grid => "grid",
+            SamplePattern::Stratified => "stratified",
+            SamplePattern::BlueNoise => "blue noise",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<SamplePattern> {
+        Self::all().into_iter().find(|p| p.name() == name)
+    }
+}
+
+/// Maps the linear radiance of the shaded pass into the displayable range.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy, Debug, Default)]
+pub enum ToneMapping {
+    /// Clips values above 1.0.
+    #[default]
+    None,
+    Reinhard,
+    /// The ACES filmic curve fitted by Narkowicz 2015.
+    Aces,
+}
+
+impl ToneMapping {
+    pub fn all() -> [ToneMapping; 3] {
+        [ToneMapping::None, ToneMapping::Reinhard, ToneMapping::Aces]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ToneMapping::None => "none",
+            ToneMapping::Reinhard => "reinhard",
+            ToneMapping::Aces => "aces",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<ToneMapping> {
+        Self::all().into_iter().find(|t| t.name() == name)
+    }
+}
+
+/// The floating point precision of the distance evaluations while marching.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy, Debug, Default)]
+pub enum Precision {
+    /// f64, used for exports.
+    #[default]
+    Double,
+    /// f32, faster for interactive previews.
+    Single,
+}
+
+impl Precision {
+    pub fn all() -> [Precision; 2] {
+        [Precision::Double, Precision::Single]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Precision::Double => "double",
+            Precision::Single => "single",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Precision> {
+        Self::all().into_iter().find(|p| p.name() == name)
+    }
+}
+
+/// The ray marching parameters of the project.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+#[serde(default)]
+pub struct RenderSettings {
+    /// The distance below which a ray counts as a surface hit, used by rays without a pixel
+    /// footprint and when the pixel threshold is 0.0.
+    pub iso_value: f64,
+    /// The hit threshold of camera rays as a fraction of their pixel footprint.
+    pub pixel_threshold: f64,
+    /// The maximum distance a ray travels.
+    pub t_max: f64,
+    pub max_steps: usize,
+    /// Multiplier of each sphere tracing step, values below 1.0 under-relax non-exact fields.
+    pub step_size: f64,
+
+    pub method: MarchMethod,
+    /// The over-relaxation factor in [1, 2) of the over-relaxed method.
+    pub relaxation: f64,
+    /// Refine hits which overshot the surface by bisection.
+    pub refine: bool,
+    /// March neighboring primary rays together in packets, used by the naive method without
+    /// hit refinement.
+    pub packets: bool,
+    /// Bake objects which are not being edited into distance caches.
+    pub distance_cache: bool,
+    /// Trace soft shadow rays towards the lights, can be disabled for interactive previews.
+    pub shadows: bool,
+    /// The precision of the distance evaluations, exports always use double precision.
+    pub precision: Precision,
+
+    pub antialiasing: AntiAliasing,
+    /// The number of samples per pixel along each axis of supersampling.
+    pub aa: usize,
+    pub sampling: SamplePattern,
+    /// Only take all samples for pixels whose first samples disagree.
+    pub adaptive: bool,
+    /// The color difference of the first samples above which a pixel takes all samples.
+    pub adaptive_threshold: f64,
+
+    /// The number of reflection and refraction bounces of the shaded pass.
+    pub max_depth: usize,
+    /// The number of samples per pixel after which the path tracer stops refining the frame.
+    pub max_samples: u32,
+
+    /// The exposure of the shaded pass in stops.
+    pub exposure: f64,
+    pub tone_mapping: ToneMapping,
+
+    /// The number of distance samples taken along the normal for ambient occlusion.
+    pub ao_steps: usize,
+    pub ao_strength: f64,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RenderSettings {
+    pub fn new() -> Self {
+        Self {
+            iso_value: 0.0001,
+            pixel_threshold: 0.5,
+            t_max: 10.0,
+            max_steps: 100,
+            step_size: 1.0,
+
+            method: MarchMethod::Naive,
+            relaxation: 1.6,
+            refine: false,
+            packets: true,
+            distance_cache: false,
+            shadows: true,
+            precision: Precision::Double,
+
+            antialiasing: AntiAliasing::Supersampling,
+            aa: 1,
+            sampling: SamplePattern::Grid,
+            adaptive: false,
+            adaptive_threshold: 0.05,
+
+            max_depth: 2,
+            max_samples: 256,
+
+            exposure: 0.0,
+            tone_mapping: ToneMapping::None,
+
+            ao_steps: 5,
+            ao_strength: 3.0,
+        }
+    }
+
+    /// Turns off the preview shortcuts which trade accuracy for speed, used for exports. The
+    /// scene is evaluated exactly in double precision and without distance caches, so the
+    /// output does not depend on earlier renders of the tracer.
+    pub fn make_exact(&mut self) {
+        self.precision = Precision::Double;
+        self.distance_cache = false;
+    }
+}
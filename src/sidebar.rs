@@ -247,6 +247,7 @@ impl Sidebar {
                             .send_widget_state_changed(&id, TheWidgetState::Selected);
 
                         project.add_object(object);
+                        redraw = true;
                     }
                 }
             }
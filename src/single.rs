@@ -0,0 +1,298 @@
+use crate::prelude::*;
+use exmex::prelude::*;
+use rayon::prelude::*;
+
+/// The largest accepted difference between single and double precision distances.
+pub const DISTANCE_TOLERANCE: f64 = 1e-4;
+/// The largest accepted difference between the single and double precision hits of a ray.
+pub const HIT_TOLERANCE: f64 = 1e-3;
+/// The fraction of rays whose hits may disagree, grazing rays can flip between hit and miss.
+pub const RAY_TOLERANCE: f64 = 0.005;
+
+/// A single precision copy of the distance field of a scene, used for faster interactive
+/// previews. The rotation and scale of each object are baked into a matrix.
+#[derive(Default)]
+pub struct SingleScene {
+    objects: Vec<SingleObject>,
+    expr: Option<FlatEx<f32>>,
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+struct SingleObject {
+    primitive: Primitive,
+    position: Vec3f,
+    /// The columns of the world to local matrix, including the inverse scale.
+    matrix: [Vec3f; 3],
+    scale: f32,
+    op: CsgOp,
+    smoothing: f64,
+}
+
+impl SingleScene {
+    /// Converts the objects and the displacement expression to single precision.
+    pub fn new(objects: &[Object], displacement: &str) -> Self {
+        Self {
+            objects: objects.iter().map(SingleObject::new).collect(),
+            expr: exmex::parse::<f32>(displacement).ok(),
+        }
+    }
+
+    /// Evaluates the scene distance at p, only the sorted active objects are evaluated like in
+    /// [`Tracer::map_active`].
+    pub fn distance(&self, p: Vec3f, active: &[usize]) -> f32 {
+        let mut d = f32::empty();
+        let mut active = active.iter().peekable();
+
+        for (index, object) in self.objects.iter().enumerate() {
+            if active.next_if_eq(&&index).is_some() {
+                d = object
+                    .op
+                    .apply_distance(d, object.distance(p), object.smoothing);
+            } else if object.op == CsgOp::Intersection {
+                d = f32::empty();
+            }
+        }
+
+        if let Some(expr) = &self.expr {
+            if let Ok(v) = expr.eval(&[p.x]) {
+                d += v;
+            }
+        }
+
+        d
+    }
+}
+
+impl SingleObject {
+    fn new(object: &Object) -> Self {
+        let transform = &object.transform;
+
+        // The transform is linear after removing the position, its columns are the local
+        // images of the unit vectors.
+        let column = |e: Vec3d| {
+            let c = transform.to_local(transform.position + e);
+            vec3f(c.x as f32, c.y as f32, c.z as f32)
+        };
+
+        Self {
+            primitive: object.primitive,
+            position: vec3f(
+                transform.position.x as f32,
+                transform.position.y as f32,
+                transform.position.z as f32,
+            ),
+            matrix: [
+                column(vec3d(1.0, 0.0, 0.0)),
+                column(vec3d(0.0, 1.0, 0.0)),
+                column(vec3d(0.0, 0.0, 1.0)),
+            ],
+            scale: transform.scale as f32,
+            op: object.op,
+            smoothing: object.smoothing,
+        }
+    }
+
+    /// Returns the signed distance of the world space point p, see [`Object::distance`].
+    #[inline(always)]
+    fn distance(&self, p: Vec3f) -> f32 {
+        let q = p - self.position;
+        let [a, b, c] = self.matrix;
+        let local = a * q.x + b * q.y + c * q.z;
+        self.primitive
+            .eval(&Scalar3::new(local.x, local.y, local.z))
+            * self.scale
+    }
+}
+
+/// Renders the primary rays of the project in single and double precision, returns the largest
+/// distance difference at points along the rays and the fraction of rays whose hits disagree.
+pub fn compare_precision(project: &Project, width: usize, height: usize) -> (f64, f64) {
+    let mut double = Tracer::new();
+    let mut project = project.clone();
+    project.settings.precision = Precision::Double;
+    double.prepare(&project);
+
+    let mut single = Tracer::new();
+    project.settings.precision = Precision::Single;
+    single.prepare(&project);
+
+    let camera = &project.camera;
+    let width_f = width as f64;
+    let height_f = height as f64;
+    let ray = |i: usize| {
+        let uv = vec2d((i % width) as f64 / width_f, (i / width) as f64 / height_f);
+        camera.create_camera_ray(
+            project.camera_mode,
+            uv,
+            vec2d(width_f, height_f),
+            vec2d(0.0, 0.0),
+        )
+    };
+
+    let count = width * height;
+    let t_max = project.settings.t_max;
+    let all: Vec<usize> = (0..project.objects.len()).collect();
+
+    let mut rng = Pcg32::new(7);
+    let mut distance_error: f64 = 0.0;
+    for _ in 0..1 << 16 {
+        let i = (rng.next_f64() * count as f64) as usize;
+        let p = ray(i.min(count - 1)).at(rng.next_f64() * t_max);
+        let error = (double.march_distance(p, &all) - single.march_distance(p, &all)).abs();
+        distance_error = distance_error.max(error);
+    }
+
+    let mismatches = (0..count)
+        .into_par_iter()
+        .filter(|i| {
+            let ray = ray(*i);
+            let a = double.march(&ray, t_max);
+            let b = single.march(&ray, t_max);
+            a.hit() != b.hit() || (a.hit() && (a.t - b.t).abs() > HIT_TOLERANCE)
+        })
+        .count();
+
+    (distance_error, mismatches as f64 / count as f64)
+}
+
+/// Small scenes covering every primitive, transforms and the smooth CSG operations.
+pub fn sample_scenes() -> Vec<(String, Project)> {
+    let object = |primitive: Primitive, position: Vec3d, op: CsgOp, smoothing: f64| {
+        let mut object = Object::new();
+        object.primitive = primitive;
+        object.transform.position = position;
+        object.op = op;
+        object.smoothing = smoothing;
+        object
+    };
+    let scene = |objects: Vec<Object>| {
+        let mut project = Project::new();
+        project.objects = objects;
+        project
+    };
+
+    let primitives = scene(vec![
+        object(
+            Primitive::Sphere { radius: 0.5 },
+            vec3d(-1.5, 0.0, 0.0),
+            CsgOp::Union,
+            0.0,
+        ),
+        object(
+            Primitive::Box {
+                size: vec3d(0.4, 0.4, 0.4),
+                rounding: 0.05,
+            },
+            vec3d(-0.5, 0.0, 0.0),
+            CsgOp::Union,
+            0.0,
+        ),
+        object(
+            Primitive::Torus {
+                radius: 0.4,
+                thickness: 0.1,
+            },
+            vec3d(0.5, 0.0, 0.0),
+            CsgOp::Union,
+            0.0,
+        ),
+        object(
+            Primitive::Capsule {
+                height: 0.8,
+                radius: 0.2,
+            },
+            vec3d(1.5, -0.4, 0.0),
+            CsgOp::Union,
+            0.0,
+        ),
+    ]);
+
+    let mut transforms = scene(vec![
+        object(
+            Primitive::Box {
+                size: vec3d(0.6, 0.3, 0.2),
+                rounding: 0.0,
+            },
+            vec3d(-0.8, 0.2, 0.0),
+            CsgOp::Union,
+            0.0,
+        ),
+        object(
+            Primitive::Torus {
+                radius: 0.5,
+                thickness: 0.15,
+            },
+            vec3d(0.8, 0.0, -0.5),
+            CsgOp::Union,
+            0.0,
+        ),
+    ]);
+    transforms.objects[0].transform.rotation = vec3d(30.0, 45.0, 10.0);
+    transforms.objects[0].transform.scale = 1.5;
+    transforms.objects[1].transform.rotation = vec3d(70.0, 0.0, -20.0);
+    transforms.objects[1].transform.scale = 0.75;
+
+    let csg = scene(vec![
+        object(
+            Primitive::Box {
+                size: vec3d(0.8, 0.8, 0.8),
+                rounding: 0.1,
+            },
+            Vec3d::zero(),
+            CsgOp::Union,
+            0.0,
+        ),
+        object(
+            Primitive::Sphere { radius: 0.7 },
+            vec3d(0.6, 0.6, 0.6),
+            CsgOp::Union,
+            0.3,
+        ),
+        object(
+            Primitive::Capsule {
+                height: 2.0,
+                radius: 0.3,
+            },
+            vec3d(0.0, -1.0, 0.0),
+            CsgOp::Subtraction,
+            0.1,
+        ),
+        object(
+            Primitive::Sphere { radius: 1.3 },
+            Vec3d::zero(),
+            CsgOp::Intersection,
+            0.2,
+        ),
+    ]);
+
+    vec![
+        (str!("primitives"), primitives),
+        (str!("transforms"), transforms),
+        (str!("smooth csg"), csg),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn single_matches_double() {
+        for (name, project) in sample_scenes() {
+            let (distance_error, ray_mismatches) = compare_precision(&project, 80, 60);
+            assert!(
+                distance_error <= DISTANCE_TOLERANCE,
+                "{}: distance error {}",
+                name,
+                distance_error
+            );
+            assert!(
+                ray_mismatches <= RAY_TOLERANCE,
+                "{}: {}% of the hits differ by more than {}",
+                name,
+                ray_mismatches * 100.0,
+                HIT_TOLERANCE
+            );
+        }
+    }
+}
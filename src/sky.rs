@@ -0,0 +1,227 @@
+use crate::prelude::*;
+use std::f64::consts::PI;
+
+/// The analytic daylight sky, used when no environment map is loaded.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct Sky {
+    /// Off by default so that projects saved without a sky keep their look and lights, new
+    /// projects turn it on.
+    pub enabled: bool,
+
+    /// The elevation of the sun above the horizon in degrees.
+    pub sun_elevation: f64,
+    /// The azimuth of the sun in degrees, 0 points towards -z.
+    pub sun_azimuth: f64,
+    /// The haziness of the atmosphere, from 2 (clear) to 10 (hazy).
+    pub turbidity: f64,
+
+    pub intensity: f64,
+    pub sun_intensity: f64,
+}
+
+impl Default for Sky {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Sky {
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+
+            sun_elevation: 45.0,
+            sun_azimuth: 30.0,
+            turbidity: 3.0,
+
+            intensity: 0.05,
+            sun_intensity: 3.0,
+        }
+    }
+
+    /// The normalized direction towards the sun.
+    pub fn sun_direction(&self) -> Vec3d {
+        let elevation = self.sun_elevation.to_radians();
+        let azimuth = self.sun_azimuth.to_radians();
+        vec3d(
+            elevation.cos() * azimuth.sin(),
+            elevation.sin(),
+            -elevation.cos() * azimuth.cos(),
+        )
+    }
+}
+
+/// The Preetham et al. 1999 sky model, precomputed for the sun position and turbidity.
+pub struct SkyModel {
+    pub sky: Sky,
+
+    sun: Vec3d,
+    sun_color: Vec3d,
+
+    /// Perez coefficients for the Y, x and y channels.
+    perez: [[f64; 5]; 3],
+    /// The zenith values divided by the Perez function at the zenith.
+    zenith: [f64; 3],
+
+    sh: [Vec3d; 9],
+}
+
+impl SkyModel {
+    pub fn new(sky: &Sky) -> Self {
+        let t = sky.turbidity.clamp(1.7, 10.0);
+        let sun = sky.sun_direction();
+        let theta_s = sun.y.clamp(0.0, 1.0).acos();
+
+        let perez = [
+            [
+                0.1787 * t - 1.4630,
+                -0.3554 * t + 0.4275,
+                -0.0227 * t + 5.3251,
+                0.1206 * t - 2.5771,
+                -0.0670 * t + 0.3703,
+            ],
+            [
+                -0.0193 * t - 0.2592,
+                -0.0665 * t + 0.0008,
+                -0.0004 * t + 0.2125,
+                -0.0641 * t - 0.8989,
+                -0.0033 * t + 0.0452,
+            ],
+            [
+                -0.0167 * t - 0.2608,
+                -0.0950 * t + 0.0092,
+                -0.0079 * t + 0.2102,
+                -0.0441 * t - 1.6537,
+                -0.0109 * t + 0.0529,
+            ],
+        ];
+
+        let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * theta_s);
+        let zenith_y = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+
+        let t2 = t * t;
+        let th = theta_s;
+        let th2 = th * th;
+        let th3 = th2 * th;
+        let zenith_x = t2 * (0.00166 * th3 - 0.00375 * th2 + 0.00209 * th)
+            + t * (-0.02903 * th3 + 0.06377 * th2 - 0.03202 * th + 0.00394)
+            + (0.11693 * th3 - 0.21196 * th2 + 0.06052 * th + 0.25886);
+        let zenith_yc = t2 * (0.00275 * th3 - 0.00610 * th2 + 0.00317 * th)
+            + t * (-0.04214 * th3 + 0.08970 * th2 - 0.04153 * th + 0.00516)
+            + (0.15346 * th3 - 0.26756 * th2 + 0.06670 * th + 0.26688);
+
+        let zenith = [
+            zenith_y / perez_f(&perez[0], 0.0, theta_s),
+            zenith_x / perez_f(&perez[1], 0.0, theta_s),
+            zenith_yc / perez_f(&perez[2], 0.0, theta_s),
+        ];
+
+        let mut model = Self {
+            sky: sky.clone(),
+
+            sun,
+            sun_color: sun_transmittance(theta_s, t) * sky.sun_intensity,
+
+            perez,
+            zenith,
+
+            sh: [Vec3d::zero(); 9],
+        };
+
+        model.sh = project_sh(32, 16, |_, _, d| model.sky_radiance(d));
+        model
+    }
+
+    /// The radiance of the sky including the sun disk in the direction d.
+    pub fn sample(&self, d: Vec3d) -> Vec3d {
+        let mut c = self.sky_radiance(d);
+
+        // The sun disk with an angular radius of 0.5 degrees
+        if dot(d, self.sun) > 0.99996 {
+            c += self.sun_color * 100.0;
+        }
+        c
+    }
+
+    /// The cosine weighted irradiance of the sky around n, divided by PI.
+    pub fn irradiance(&self, n: Vec3d) -> Vec3d {
+        sh_irradiance(&self.sh, n)
+    }
+
+    /// The directional light representing the sun.
+    pub fn sun_light(&self) -> Light {
+        Light {
+            name: str!("Sun"),
+            id: Uuid::nil(),
+
+            light_type: LightType::Directional,
+            position: self.sun,
+            color: self.sun_color,
+            intensity: 1.0,
+
+            shadow_k: 16.0,
+        }
+    }
+
+    /// The radiance of the sky without the sun disk, the ground below the horizon is a darkened
+    /// copy of the horizon.
+    pub fn sky_radiance(&self, d: Vec3d) -> Vec3d {
+        let ground = d.y < 0.0;
+        let d = normalize(vec3d(d.x, d.y.max(0.001), d.z));
+
+        let theta = d.y.acos();
+        let gamma = dot(d, self.sun).clamp(-1.0, 1.0).acos();
+
+        let y = self.zenith[0] * perez_f(&self.perez[0], theta, gamma);
+        let x = self.zenith[1] * perez_f(&self.perez[1], theta, gamma);
+        let yc = self.zenith[2] * perez_f(&self.perez[2], theta, gamma);
+
+        let mut c = xyy_to_rgb(x, yc, y) * self.sky.intensity;
+        if ground {
+            c = c * 0.3;
+        }
+        vec3d(c.x.max(0.0), c.y.max(0.0), c.z.max(0.0))
+    }
+}
+
+/// The Perez luminance distribution function.
+fn perez_f(c: &[f64; 5], theta: f64, gamma: f64) -> f64 {
+    let cos_gamma = gamma.cos();
+    (1.0 + c[0] * (c[1] / theta.cos().max(0.001)).exp())
+        * (1.0 + c[2] * (c[3] * gamma).exp() + c[4] * cos_gamma * cos_gamma)
+}
+
+/// Converts the CIE xyY color to linear sRGB.
+fn xyy_to_rgb(x: f64, y: f64, luminance: f64) -> Vec3d {
+    if y <= 0.0 {
+        return Vec3d::zero();
+    }
+    let cx = x * luminance / y;
+    let cz = (1.0 - x - y) * luminance / y;
+    vec3d(
+        3.2406 * cx - 1.5372 * luminance - 0.4986 * cz,
+        -0.9689 * cx + 1.8758 * luminance + 0.0415 * cz,
+        0.0557 * cx - 0.2040 * luminance + 1.0570 * cz,
+    )
+}
+
+/// The attenuation of sunlight by Rayleigh and aerosol scattering for the sun zenith angle,
+/// following the appendix of Preetham et al.
+fn sun_transmittance(theta_s: f64, turbidity: f64) -> Vec3d {
+    let theta_deg = theta_s.to_degrees().min(93.0);
+    let air_mass = 1.0 / (theta_s.cos() + 0.15 * (93.885 - theta_deg).powf(-1.253));
+
+    let beta = 0.04608 * turbidity - 0.04586;
+    let alpha = 1.3;
+
+    // Wavelengths of red, green and blue in micrometers
+    let lambda = [0.65, 0.57, 0.475];
+    let mut t = [0.0; 3];
+    for (t, lambda) in t.iter_mut().zip(lambda) {
+        let rayleigh = 0.008735 * f64::powf(lambda, -4.08) * air_mass;
+        let aerosol = beta * f64::powf(lambda, -alpha) * air_mass;
+        *t = (-(rayleigh + aerosol)).exp();
+    }
+
+    vec3d(t[0], t[1], t[2])
+}
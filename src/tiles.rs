@@ -0,0 +1,78 @@
+use crate::prelude::*;
+
+/// A rectangle of pixels on the screen, y runs from the bottom of the screen up like the camera
+/// uv coordinates.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct Tile {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Tile {
+    /// The top row of the tile in a buffer of the given height, buffer rows run from the top of
+    /// the screen down.
+    pub fn buffer_y(&self, height: usize) -> usize {
+        height - self.y - self.height
+    }
+
+    /// Creates a buffer of the size of the tile.
+    pub fn buffer(&self) -> TheRGBABuffer {
+        TheRGBABuffer::new(TheDim::new(0, 0, self.width as i32, self.height as i32))
+    }
+}
+
+/// Splits the screen into tiles of the given size, ordered by the distance of their center to
+/// the center of the screen so that the interesting part appears first.
+pub fn center_out_tiles(width: usize, height: usize, size: usize) -> Vec<Tile> {
+    let mut tiles = Vec::new();
+    for y in (0..height).step_by(size) {
+        for x in (0..width).step_by(size) {
+            tiles.push(Tile {
+                x,
+                y,
+                width: size.min(width - x),
+                height: size.min(height - y),
+            });
+        }
+    }
+
+    let center = vec2d(width as f64 * 0.5, height as f64 * 0.5);
+    let distance = |tile: &Tile| {
+        let c = vec2d(
+            tile.x as f64 + tile.width as f64 * 0.5,
+            tile.y as f64 + tile.height as f64 * 0.5,
+        );
+        length(c - center)
+    };
+    tiles.sort_by(|a, b| distance(a).total_cmp(&distance(b)));
+
+    tiles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tiles_cover_every_pixel_once() {
+        for (width, height, size) in [(64, 64, 16), (100, 37, 16), (5, 3, 8), (1, 1, 1)] {
+            let mut covered = vec![0; width * height];
+            for tile in center_out_tiles(width, height, size) {
+                for y in tile.y..tile.y + tile.height {
+                    for x in tile.x..tile.x + tile.width {
+                        covered[y * width + x] += 1;
+                    }
+                }
+            }
+            assert!(
+                covered.iter().all(|c| *c == 1),
+                "{}x{} in tiles of {}",
+                width,
+                height,
+                size
+            );
+        }
+    }
+}
@@ -1,144 +1,1333 @@
 use crate::prelude::*;
 use exmex::prelude::*;
 use rayon::prelude::*;
+use rustc_hash::FxHashMap;
+use std::cell::Cell;
 use theframework::prelude::*;
 
+/// The displacement expression added to the distance of the scene.
+const DISPLACEMENT: &str = "sin(x * 20.0 - 1.0) * 0.1";
+
+/// The size in pixels of the screen tiles tested for empty space before marching.
+const TILE_SIZE: usize = 16;
+
+/// The neighborhood radius of the features pass in pixels.
+const FEATURE_PIXELS: f64 = 2.0;
+
+/// The angle in radians between neighboring normals above which a feature counts as sharp.
+const SHARP_ANGLE: f64 = 0.5;
+
+/// Materials below this roughness trace reflection rays.
+const GLOSSY_ROUGHNESS: f64 = 0.3;
+
+/// The size in pixels of the tiles rendered in parallel.
+pub const RENDER_TILE_SIZE: usize = 64;
+
+thread_local! {
+    /// The objects crossed by the current ray, reused by all rays of a thread.
+    static ACTIVE: Cell<Vec<usize>> = const { Cell::new(Vec::new()) };
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy, Debug)]
+pub enum RenderPass {
+    Shaded,
+    Depth,
+    Normal,
+    ObjectId,
+    Albedo,
+    AmbientOcclusion,
+    Iterations,
+    MarchDebug,
+    /// Sharp features of the surface, from the angle between neighboring normals.
+    Features,
+}
+
+impl RenderPass {
+    /// All passes in the order of the render view selector.
+    pub fn all() -> [RenderPass; 9] {
+        [
+            RenderPass::Shaded,
+            RenderPass::Depth,
+            RenderPass::Normal,
+            RenderPass::ObjectId,
+            RenderPass::Albedo,
+            RenderPass::AmbientOcclusion,
+            RenderPass::Iterations,
+            RenderPass::MarchDebug,
+            RenderPass::Features,
+        ]
+    }
+
+    /// The name of the pass, used for the command line and exported file names.
+    pub fn name(&self) -> &'static str {
+        match self {
+            RenderPass::Shaded => "shaded",
+            RenderPass::Depth => "depth",
+            RenderPass::Normal => "normal",
+            RenderPass::ObjectId => "object-id",
+            RenderPass::Albedo => "albedo",
+            RenderPass::AmbientOcclusion => "ao",
+            RenderPass::Iterations => "iterations",
+            RenderPass::MarchDebug => "march-debug",
+            RenderPass::Features => "features",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<RenderPass> {
+        Self::all().into_iter().find(|pass| pass.name() == name)
+    }
+}
+
+/// Why sphere tracing of a ray stopped.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Termination {
+    Hit,
+    /// The ray left the scene at t_max.
+    Escaped,
+    /// The step limit was reached before a surface or t_max, usually a sign of a
+    /// non-conservative distance field.
+    StepLimit,
+}
+
+/// The outcome of sphere tracing a ray.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct March {
+    pub termination: Termination,
+    pub t: f64,
+    pub iterations: usize,
+    /// The last distance sample, negative values at a hit mean the ray overshot the surface.
+    pub distance: f64,
+}
+
+impl March {
+    /// A ray which left the scene without any steps.
+    pub fn escaped(t: f64) -> Self {
+        Self {
+            termination: Termination::Escaped,
+            t,
+            iterations: 0,
+            distance: f64::MAX,
+        }
+    }
+
+    pub fn hit(&self) -> bool {
+        self.termination == Termination::Hit
+    }
+}
+
+/// A surface which passes through the cone of a pixel without being hit by its center ray.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct ConeEdge {
+    /// The distance along the ray where the surface comes closest.
+    pub t: f64,
+    /// The fraction of the pixel covered by the surface.
+    pub coverage: f64,
+}
+
+impl ConeEdge {
+    /// The fraction of the pixel covered by a surface at the given signed distance from the
+    /// center ray, relative to the width of the cone. Half of the pixel is covered where the
+    /// surface passes through the center.
+    fn coverage(ratio: f64) -> f64 {
+        (0.5 - ratio).clamp(0.0, 1.0)
+    }
+
+    /// Keeps the edge with the larger coverage, ratio is the distance to the surface over the
+    /// width of the cone at t.
+    fn nearest(edge: &mut Option<ConeEdge>, ratio: f64, t: f64) {
+        let coverage = Self::coverage(ratio);
+        if coverage > edge.map_or(0.0, |e| e.coverage) {
+            *edge = Some(ConeEdge { t, coverage });
+        }
+    }
+}
+
+/// The result of a cone march.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct ConeMarch {
+    pub march: March,
+    /// The surface grazed closest by the cone before the center ray hit or escaped.
+    pub edge: Option<ConeEdge>,
+    /// For hits close to a silhouette, the part of the pixel beside the hit surface, t is where
+    /// the center ray leaves the surface again.
+    pub beside: Option<ConeEdge>,
+}
+
 pub struct Tracer {
     expr: Option<FlatEx<f64>>,
+    /// The displacement as an expression tree, evaluated over intervals and dual numbers.
+    displacement: Option<Expr>,
+    objects: Vec<Object>,
+    /// The baked distance fields of the objects, by object index.
+    caches: Vec<Option<DistanceCache>>,
+    /// The single precision copy of the scene, only built for single precision settings.
+    single: SingleScene,
+    lights: Vec<Light>,
+    environment: Option<EnvironmentMap>,
+    sky: Option<SkyModel>,
+    settings: RenderSettings,
+    bvh: Bvh,
+
+    /// The pass rendered by render().
+    pub pass: RenderPass,
 }
 
 #[allow(clippy::new_without_default)]
 impl Tracer {
     pub fn new() -> Self {
-        Self { expr: None }
+        Self {
+            expr: None,
+            displacement: None,
+            objects: Vec::new(),
+            caches: Vec::new(),
+            single: SingleScene::default(),
+            lights: Vec::new(),
+            environment: None,
+            sky: None,
+            settings: RenderSettings::default(),
+            bvh: Bvh::default(),
+
+            pass: RenderPass::Shaded,
+        }
+    }
+
+    pub fn render(&mut self, buffer: &mut TheRGBABuffer, project: &Project) {
+        self.render_pass(buffer, project, self.pass);
     }
 
-    pub fn render(&mut self, buffer: &mut TheRGBABuffer, _project: &Project) {
-        let _start = self.get_time();
+    /// Renders the given pass of the project into the buffer.
+    pub fn render_pass(&mut self, buffer: &mut TheRGBABuffer, project: &Project, pass: RenderPass) {
+        let width = buffer.dim().width as usize;
+        let height = buffer.dim().height as usize;
+
+        let linear = self.render_linear(project, pass, width, height);
+        linear.encode(buffer, pass, &self.settings);
+    }
 
-        //let stride = buffer.stride();
-        //let height = buffer.dim().height;
+    /// Renders the given pass of the project into a linear float image of the given size,
+    /// before exposure and tone mapping.
+    pub fn render_linear(
+        &mut self,
+        project: &Project,
+        pass: RenderPass,
+        width: usize,
+        height: usize,
+    ) -> FloatBuffer {
+        self.prepare(project);
 
+        let tiles: Vec<(Tile, FloatBuffer)> = center_out_tiles(width, height, RENDER_TILE_SIZE)
+            .into_par_iter()
+            .map(|tile| {
+                let mut tile_buffer = FloatBuffer::new(tile.width, tile.height);
+                self.render_region_linear(
+                    &mut tile_buffer,
+                    project,
+                    pass,
+                    (tile.x, tile.y),
+                    (width, height),
+                );
+                (tile, tile_buffer)
+            })
+            .collect();
+
+        let mut buffer = FloatBuffer::new(width, height);
+        for (tile, tile_buffer) in &tiles {
+            buffer.copy_tile(tile_buffer, tile.x, tile.buffer_y(height));
+        }
+        buffer
+    }
+
+    /// Renders the region of the screen starting at the pixel origin into the buffer, which has
+    /// the size of the region. The scene has to be prepared, the region is rendered on the
+    /// calling thread.
+    pub fn render_region(
+        &self,
+        buffer: &mut TheRGBABuffer,
+        project: &Project,
+        pass: RenderPass,
+        origin: (usize, usize),
+        screen: (usize, usize),
+    ) {
         let width = buffer.dim().width as usize;
-        let width_f = buffer.dim().width as f64;
-        let height_f = buffer.dim().height as f64;
+        let height = buffer.dim().height as usize;
+
+        let mut linear = FloatBuffer::new(width, height);
+        self.render_region_linear(&mut linear, project, pass, origin, screen);
+        linear.encode(buffer, pass, &self.settings);
+    }
 
-        let ro = vec3d(0.0, 1.0, 5.0);
-        let rd = vec3d(0.0, 0.0, 0.0);
-        let fov = 70.0;
-        let camera_mode = CameraMode::Pinhole;
+    /// Renders the linear colors of the region like [`Tracer::render_region`].
+    pub fn render_region_linear(
+        &self,
+        buffer: &mut FloatBuffer,
+        project: &Project,
+        pass: RenderPass,
+        origin: (usize, usize),
+        screen: (usize, usize),
+    ) {
+        let width = buffer.width();
+        let height = buffer.height();
+        let width_f = screen.0 as f64;
+        let height_f = screen.1 as f64;
 
-        let aa = 1;
-        let aa_f = aa as f64;
+        let camera = project.camera.clone();
+        let camera_mode = project.camera_mode;
+        let forward = normalize(camera.center - camera.origin);
+
+        let cone = self.settings.antialiasing == AntiAliasing::Cone;
+        let aa = if cone { 1 } else { self.settings.aa.max(1) };
+        let sampling = self.settings.sampling;
+
+        // The corner cells come first, adaptive sampling only continues with the remaining
+        // cells for pixels whose corner samples disagree.
+        let mut cells: Vec<(usize, usize)> = Vec::with_capacity(aa * aa);
+        let corners = [(0, 0), (aa - 1, aa - 1), (aa - 1, 0), (0, aa - 1)];
+        for cell in corners
+            .into_iter()
+            .chain((0..aa * aa).map(|i| (i / aa, i % aa)))
+        {
+            if !cells.contains(&cell) {
+                cells.push(cell);
+            }
+        }
+        let first_samples = if self.settings.adaptive {
+            cells.len().min(corners.len())
+        } else {
+            cells.len()
+        };
 
-        self.expr = exmex::parse::<f64>("sin(x * 20.0 - 1.0) * 0.1").ok();
+        // Tiles which are proven empty only show the background
+        let tiles_x = width.div_ceil(TILE_SIZE);
+        let tiles_y = height.div_ceil(TILE_SIZE);
+        let empty_tiles: Vec<bool> = (0..tiles_x * tiles_y)
+            .map(|tile| {
+                let x = origin.0 + (tile % tiles_x) * TILE_SIZE;
+                let y = origin.1 + (tile / tiles_x) * TILE_SIZE;
+                self.tile_is_empty(
+                    &camera,
+                    camera_mode,
+                    forward,
+                    (
+                        x,
+                        y,
+                        (x + TILE_SIZE).min(origin.0 + width),
+                        (y + TILE_SIZE).min(origin.1 + height),
+                    ),
+                    vec2d(width_f, height_f),
+                )
+            })
+            .collect();
+        let escaped = March::escaped(self.settings.t_max);
 
         let pixels = buffer.pixels_mut();
-        let iso_value = 0.0001_f64;
 
         pixels
-            .par_rchunks_exact_mut(width * 4)
+            .rchunks_exact_mut(width * 4)
             .enumerate()
             .for_each(|(j, line)| {
-                for (i, pixel) in line.chunks_exact_mut(4).enumerate() {
-                    let i = j * width + i;
+                // Neighboring pixels of a line are marched together
+                for (g, group) in line.chunks_mut(4 * LANES).enumerate() {
+                    let first = g * LANES;
+                    let count = group.len() / 4;
+
+                    let tile = (j / TILE_SIZE) * tiles_x + first / TILE_SIZE;
+                    let empty = empty_tiles[tile];
 
-                    let xx = (i % width) as f64;
-                    let yy = (i / width) as f64;
+                    let yy = (origin.1 + j) as f64;
 
-                    let uv = vec2d(xx, yy);
+                    let mut totals = [Vec4d::zero(); LANES];
+                    let mut active = [true; LANES];
+                    let mut lo = [vec3d(f64::MAX, f64::MAX, f64::MAX); LANES];
+                    let mut hi = [vec3d(f64::MIN, f64::MIN, f64::MIN); LANES];
 
-                    let mut total = Vec4d::zero();
+                    for (sample, cell) in cells.iter().enumerate() {
+                        if sample == first_samples {
+                            for ((active, lo), hi) in active.iter_mut().zip(&lo).zip(&hi) {
+                                let range = *hi - *lo;
+                                *active = range.x.max(range.y).max(range.z)
+                                    > self.settings.adaptive_threshold;
+                            }
+                            if !active.iter().any(|a| *a) {
+                                break;
+                            }
+                        }
 
-                    for m in 0..aa {
-                        for n in 0..aa {
-                            let camera = Camera::new(ro, rd, fov);
+                        // A partial packet at the end of the line repeats its last ray
+                        let rays: [Ray; LANES] = std::array::from_fn(|k| {
+                            let x = origin.0 + first + k.min(count - 1);
                             let camera_offset =
-                                vec2d(m as f64 / aa_f, n as f64 / aa_f) - vec2d(0.5, 0.5);
-
-                            let mut ray = if camera_mode == CameraMode::Pinhole {
-                                camera.create_ray(
-                                    vec2d(xx / width_f, yy / height_f),
-                                    vec2d(width_f, height_f),
-                                    camera_offset,
-                                )
+                                pixel_sample(sampling, *cell, aa, (x, origin.1 + j));
+                            camera.create_camera_ray(
+                                camera_mode,
+                                vec2d(x as f64 / width_f, yy / height_f),
+                                vec2d(width_f, height_f),
+                                camera_offset,
+                            )
+                        });
+
+                        let colors: [Vec3d; LANES] = if cone && !empty {
+                            std::array::from_fn(|k| {
+                                if active[k] && k < count {
+                                    self.trace_cone(&rays[k], pass, forward)
+                                } else {
+                                    Vec3d::zero()
+                                }
+                            })
+                        } else {
+                            let marches = if empty {
+                                [escaped; LANES]
+                            } else if active.iter().all(|a| *a) {
+                                self.march_rays(&rays, self.settings.t_max)
                             } else {
-                                camera.create_ortho_ray(
-                                    vec2d(xx / width_f, yy / height_f),
-                                    vec2d(width_f, height_f),
-                                    camera_offset,
-                                )
+                                std::array::from_fn(|k| {
+                                    if active[k] {
+                                        self.march(&rays[k], self.settings.t_max)
+                                    } else {
+                                        escaped
+                                    }
+                                })
                             };
+                            std::array::from_fn(|k| {
+                                if active[k] && k < count {
+                                    self.shade_pass(&rays[k], pass, forward, &marches[k])
+                                } else {
+                                    Vec3d::zero()
+                                }
+                            })
+                        };
+
+                        for (k, c) in colors.iter().enumerate().take(count) {
+                            if active[k] {
+                                totals[k] += vec4d(c.x, c.y, c.z, 1.0);
+                                lo[k] = vec3d(lo[k].x.min(c.x), lo[k].y.min(c.y), lo[k].z.min(c.z));
+                                hi[k] = vec3d(hi[k].x.max(c.x), hi[k].y.max(c.y), hi[k].z.max(c.z));
+                            }
+                        }
+                    }
+
+                    for (pixel, total) in group.chunks_exact_mut(4).zip(totals) {
+                        // The alpha channel counts the samples of the pixel
+                        let samples = total[3];
+                        for (out, value) in pixel.iter_mut().zip([total[0], total[1], total[2]]) {
+                            *out = (value / samples) as f32;
+                        }
+                        pixel[3] = 1.0;
+                    }
+                }
+            });
+    }
 
-                            let mut color = vec4d(0.0, 0.0, 0.0, 1.0);
+    /// Takes over the scene of the project for the following distance evaluations.
+    pub fn prepare(&mut self, project: &Project) {
+        self.expr = exmex::parse::<f64>(DISPLACEMENT).ok();
+        self.displacement = Expr::parse(DISPLACEMENT);
+        self.settings = project.settings.clone();
 
-                            let mut t = iso_value;
-                            let t_max = 10.0;
+        // Objects which did not change since the last preparation are baked, edits invalidate
+        // the cache of the object.
+        let mut previous: FxHashMap<Uuid, (Object, Option<DistanceCache>)> = self
+            .objects
+            .drain(..)
+            .zip(self.caches.drain(..))
+            .map(|(object, cache)| (object.id, (object, cache)))
+            .collect();
+        self.objects = project.objects.clone();
+        self.caches = self
+            .objects
+            .iter()
+            .map(|object| match previous.remove(&object.id) {
+                Some((old, cache)) if self.settings.distance_cache && old == *object => {
+                    cache.or_else(|| Some(DistanceCache::new(object)))
+                }
+                _ => None,
+            })
+            .collect();
 
-                            let mut hit = false;
+        self.single = if self.settings.precision == Precision::Single {
+            SingleScene::new(&self.objects, DISPLACEMENT)
+        } else {
+            SingleScene::default()
+        };
 
-                            for _ in 0..100 {
-                                let p = ray.at(t);
+        self.lights = project.lights.clone();
 
-                                let d = self.distance(p);
+        let bounds: Vec<Aabb> = self
+            .objects
+            .iter()
+            .map(|object| self.object_bounds(object))
+            .collect();
+        self.bvh = Bvh::new(&bounds);
 
-                                t += d;
+        // Only decode the environment when a new one was loaded
+        if let Some(environment) = &project.environment {
+            if self.environment.as_ref().map(|e| e.id) != Some(environment.id) {
+                self.environment = EnvironmentMap::new(environment);
+            }
+            if let Some(map) = &mut self.environment {
+                map.rotation = environment.rotation;
+                map.intensity = environment.intensity;
+            }
+        } else {
+            self.environment = None;
+        }
 
-                                if d < iso_value {
-                                    hit = true;
-                                    break;
-                                } else if t > t_max {
-                                    break;
-                                }
-                            }
+        // The sky is the fallback when no environment is available
+        if self.environment.is_none() && project.sky.enabled {
+            if self.sky.as_ref().map(|s| &s.sky) != Some(&project.sky) {
+                self.sky = Some(SkyModel::new(&project.sky));
+            }
+            if let Some(sky) = &self.sky {
+                self.lights.push(sky.sun_light());
+            }
+        } else {
+            self.sky = None;
+        }
+    }
 
-                            if hit {
-                                let normal = self.normal(ray.at(t));
-                                color.x = normal.x;
-                                color.y = normal.y;
-                                color.z = normal.z;
-                                color.z = 1.0;
-                            }
+    /// The render settings of the prepared scene.
+    pub fn settings(&self) -> &RenderSettings {
+        &self.settings
+    }
 
-                            total += color;
-                        }
-                    }
+    /// The lights of the prepared scene.
+    pub fn lights(&self) -> &[Light] {
+        &self.lights
+    }
+
+    /// The radiance arriving from the background in the direction rd.
+    pub fn background(&self, rd: Vec3d) -> Vec3d {
+        if let Some(environment) = &self.environment {
+            environment.sample(rd)
+        } else if let Some(sky) = &self.sky {
+            sky.sample(rd)
+        } else {
+            Vec3d::zero()
+        }
+    }
+
+    /// The background radiance for rays scattered by a surface whose lights are sampled directly.
+    /// The sun disk is left out since the sun light already accounts for it.
+    pub fn indirect_background(&self, rd: Vec3d) -> Vec3d {
+        if let Some(environment) = &self.environment {
+            environment.sample(rd)
+        } else if let Some(sky) = &self.sky {
+            sky.sky_radiance(rd)
+        } else {
+            Vec3d::zero()
+        }
+    }
+
+    /// The diffuse irradiance of the environment or the sky around n, divided by PI.
+    pub fn irradiance(&self, n: Vec3d) -> Option<Vec3d> {
+        if let Some(environment) = &self.environment {
+            Some(environment.irradiance(n))
+        } else {
+            self.sky.as_ref().map(|sky| sky.irradiance(n))
+        }
+    }
+
+    /// Traces the ray and returns the color of the given pass, forward is the viewing direction
+    /// of the camera used for linear depth.
+    pub fn trace_pass(&self, ray: &Ray, pass: RenderPass, forward: Vec3d) -> Vec3d {
+        let march = self.march(ray, self.settings.t_max);
+        self.shade_pass(ray, pass, forward, &march)
+    }
+
+    /// Traces the ray as a cone and blends the color of the surface passing closest to the cone
+    /// by its coverage of the pixel. Hits close to a silhouette are blended with what lies
+    /// beside the surface.
+    pub fn trace_cone(&self, ray: &Ray, pass: RenderPass, forward: Vec3d) -> Vec3d {
+        let t_max = self.settings.t_max;
+        let cone = self.march_cone(ray, t_max);
+        let mut color = self.shade_pass(ray, pass, forward, &cone.march);
+
+        if matches!(
+            pass,
+            RenderPass::Iterations | RenderPass::MarchDebug | RenderPass::ObjectId
+        ) {
+            return color;
+        }
+
+        if let Some(beside) = cone.beside {
+            // Continue the center ray behind the surface
+            let behind = Ray::new(ray.at(beside.t), ray.d)
+                .with_footprint(ray.footprint_at(beside.t), ray.spread);
+            let march = self.march(&behind, t_max - beside.t);
+            let march = March {
+                t: march.t + beside.t,
+                ..march
+            };
+            let beside_color = self.shade_pass(ray, pass, forward, &march);
+            color = color * (1.0 - beside.coverage) + beside_color * beside.coverage;
+        }
+
+        if let Some(edge) = cone.edge {
+            let edge_march = March {
+                termination: Termination::Hit,
+                t: edge.t,
+                ..cone.march
+            };
+            let edge_color = self.shade_pass(ray, pass, forward, &edge_march);
+            color = color * (1.0 - edge.coverage) + edge_color * edge.coverage;
+        }
+
+        color
+    }
+
+    /// Returns the color of the given pass for the march of the ray.
+    pub fn shade_pass(&self, ray: &Ray, pass: RenderPass, forward: Vec3d, march: &March) -> Vec3d {
+        let t_max = self.settings.t_max;
+
+        match pass {
+            RenderPass::Iterations => {
+                return heatmap(march.iterations as f64 / self.settings.max_steps as f64);
+            }
+            RenderPass::MarchDebug => return march_debug_color(march, &self.settings),
+            _ => {}
+        }
+
+        if !march.hit() {
+            return match pass {
+                RenderPass::Shaded => self.background(ray.d),
+                RenderPass::Depth | RenderPass::AmbientOcclusion => Vec3d::one(),
+                _ => Vec3d::zero(),
+            };
+        }
+
+        let p = ray.at(march.t);
+        let step = self.hit_threshold(ray, march.t);
+        let bias = self.surface_bias(step);
+        match pass {
+            RenderPass::Shaded => self.shade_hit(ray, march.t, 0),
+            RenderPass::Depth => {
+                let depth = (march.t * dot(ray.d, forward) / t_max).clamp(0.0, 1.0);
+                vec3d(depth, depth, depth)
+            }
+            RenderPass::Normal => self.normal(p, step) * 0.5 + vec3d(0.5, 0.5, 0.5),
+            RenderPass::ObjectId => id_color(self.map(p).id),
+            RenderPass::Albedo => self.material(&self.map(p)).color,
+            RenderPass::AmbientOcclusion => {
+                let ao = self.ambient_occlusion(p, self.normal(p, step), bias);
+                vec3d(ao, ao, ao)
+            }
+            RenderPass::Features => {
+                // The radius follows the pixel footprint to keep the lines as wide on screen
+                let radius = (ray.footprint_at(march.t) * FEATURE_PIXELS).max(step);
+                let angle = self.feature_angle(p, radius);
+                let f = (angle / SHARP_ANGLE).min(1.0);
+                vec3d(f, f, f)
+            }
+            RenderPass::Iterations | RenderPass::MarchDebug => unreachable!(),
+        }
+    }
+
+    /// The distance below which the ray counts as hitting the surface at t. Camera rays use a
+    /// fraction of their pixel footprint, which follows the scale of the scene and grows with
+    /// the distance. Other rays use the fixed iso value.
+    #[inline(always)]
+    pub fn hit_threshold(&self, ray: &Ray, t: f64) -> f64 {
+        let footprint = ray.footprint_at(t);
+        if self.settings.pixel_threshold > 0.0 && footprint > 0.0 {
+            footprint * self.settings.pixel_threshold
+        } else {
+            self.settings.iso_value
+        }
+    }
+
+    /// The offset from the surface at which secondary rays and samples start, derived from the
+    /// hit threshold so that it follows the scale of the scene.
+    #[inline(always)]
+    pub fn surface_bias(&self, step: f64) -> f64 {
+        step.max(self.settings.iso_value) * 4.0
+    }
+
+    /// Sphere traces the ray and returns the distance to the surface if it was hit.
+    pub fn intersect(&self, ray: &Ray, t_max: f64) -> Option<f64> {
+        let march = self.march(ray, t_max);
+        if march.hit() {
+            Some(march.t)
+        } else {
+            None
+        }
+    }
+
+    /// Sphere traces the ray up to t_max.
+    pub fn march(&self, ray: &Ray, t_max: f64) -> March {
+        let mut active = ACTIVE.take();
+        let march = self.march_active(ray, t_max, &mut active);
+        ACTIVE.set(active);
+        march
+    }
+
+    /// Sphere traces the ray using the given buffer for the crossed objects.
+    fn march_active(&self, ray: &Ray, t_max: f64, active: &mut Vec<usize>) -> March {
+        // Only the objects whose bounds the ray crosses are evaluated, starting at the first
+        // bounds and ending after the last one.
+        let Some((t_start, t_end)) = self.bvh.traverse(ray, t_max, active) else {
+            return March::escaped(t_max);
+        };
+
+        let (mut march, bracket) = match self.settings.method {
+            MarchMethod::Naive => self.march_naive(ray, active, t_start, t_end),
+            MarchMethod::OverRelaxed => self.march_relaxed(ray, active, t_start, t_end),
+        };
+
+        if self.settings.refine && march.hit() && march.distance < 0.0 {
+            self.refine(ray, active, &mut march, bracket);
+        }
+
+        march
+    }
+
+    /// Sphere traces a packet of rays, in packets if the settings allow it.
+    pub fn march_rays(&self, rays: &[Ray; LANES], t_max: f64) -> [March; LANES] {
+        if self.settings.packets
+            && self.settings.method == MarchMethod::Naive
+            && !self.settings.refine
+        {
+            self.march_packet(rays, t_max)
+        } else {
+            std::array::from_fn(|k| self.march(&rays[k], t_max))
+        }
+    }
+
+    /// Plain sphere tracing which also returns the surface passing closest to the pixel cone of
+    /// the ray without being hit, the nearest local minimum of the distance to cone width ratio.
+    /// The final approach of a hit does not count as an edge, instead the center ray continues
+    /// through the hit surface to find how much of the pixel it covers.
+    pub fn march_cone(&self, ray: &Ray, t_max: f64) -> ConeMarch {
+        let mut active = ACTIVE.take();
+        let result = self.march_cone_active(ray, t_max, &mut active);
+        ACTIVE.set(active);
+        result
+    }
+
+    /// Cone traces the ray using the given buffer for the crossed objects.
+    fn march_cone_active(&self, ray: &Ray, t_max: f64, active: &mut Vec<usize>) -> ConeMarch {
+        let Some((t_start, t_end)) = self.bvh.traverse(ray, t_max, active) else {
+            return ConeMarch {
+                march: March::escaped(t_max),
+                edge: None,
+                beside: None,
+            };
+        };
+
+        let step_size = self.settings.step_size;
+        let mut t = t_start.max(self.settings.iso_value);
+
+        let mut march = March {
+            termination: Termination::StepLimit,
+            t,
+            iterations: 0,
+            distance: f64::MAX,
+        };
 
-                    let aa_aa = aa_f * aa_f;
-                    total[0] /= aa_aa;
-                    total[1] /= aa_aa;
-                    total[2] /= aa_aa;
-                    total[3] /= aa_aa;
-
-                    //pixel.copy_from_slice(&TheColor::from_vec4f(total).to_u8_array());
-                    let out = [
-                        (total[0] * 255.0) as u8,
-                        (total[1] * 255.0) as u8,
-                        (total[2] * 255.0) as u8,
-                        (total[3] * 255.0) as u8,
-                    ];
-                    pixel.copy_from_slice(&out);
+        let mut edge = None;
+        let mut prev = (f64::MAX, t);
+        let mut closing = false;
+
+        for i in 0..self.settings.max_steps {
+            let d = self.march_distance(ray.at(t), active);
+
+            march.iterations = i + 1;
+            march.distance = d;
+
+            if d < self.hit_threshold(ray, t) {
+                march.t = t + d * step_size;
+                march.termination = Termination::Hit;
+                let beside = self.cone_exit(ray, active, march.t, t_end);
+                return ConeMarch {
+                    march,
+                    edge,
+                    beside,
+                };
+            }
+
+            let width = ray.footprint_at(t);
+            let ratio = if width > 0.0 { d / width } else { f64::MAX };
+            if closing && ratio >= prev.0 {
+                ConeEdge::nearest(&mut edge, prev.0, prev.1);
+            }
+            closing = ratio < prev.0;
+            prev = (ratio, t);
+
+            t += d * step_size;
+
+            if t > t_end {
+                march.termination = Termination::Escaped;
+                break;
+            }
+        }
+
+        // The ray left while still closing in on a surface
+        if closing {
+            ConeEdge::nearest(&mut edge, prev.0, prev.1);
+        }
+
+        march.t = t;
+        ConeMarch {
+            march,
+            edge,
+            beside: None,
+        }
+    }
+
+    /// Marches the center ray through the surface it hit at t_hit and tracks how deep it gets,
+    /// the mirror image of how close a missing ray passes. Returns the uncovered part of the
+    /// pixel and where the ray leaves the surface, None if the pixel is fully covered.
+    fn cone_exit(&self, ray: &Ray, active: &[usize], t_hit: f64, t_end: f64) -> Option<ConeEdge> {
+        let mut t = t_hit;
+        let mut deepest: f64 = 0.0;
+        let mut inside = false;
+
+        for _ in 0..self.settings.max_steps {
+            let width = ray.footprint_at(t);
+            if width <= 0.0 {
+                return None;
+            }
+
+            let d = self.march_distance(ray.at(t), active);
+            deepest = deepest.min(d / width);
+            if ConeEdge::coverage(deepest) >= 1.0 {
+                return None;
+            }
+
+            if d < 0.0 {
+                inside = true;
+            } else if inside || t - t_hit > width {
+                break;
+            }
+
+            t += d.abs().max(self.hit_threshold(ray, t));
+            if t > t_end {
+                break;
+            }
+        }
+
+        Some(ConeEdge {
+            t,
+            coverage: 1.0 - ConeEdge::coverage(deepest),
+        })
+    }
+
+    /// Plain sphere tracing of all rays of the packet at once, every step evaluates the distance
+    /// of all lanes together. Finished lanes are evaluated along until all lanes are done.
+    pub fn march_packet(&self, rays: &[Ray; LANES], t_max: f64) -> [March; LANES] {
+        let iso_value = self.settings.iso_value;
+        let step_size = self.settings.step_size;
+
+        let mut marches = [March::escaped(t_max); LANES];
+        let mut done = [false; LANES];
+        let mut t = [0.0; LANES];
+        let mut t_end = [0.0; LANES];
+
+        // The packet evaluates the objects crossed by any of its rays
+        let mut active = Vec::new();
+        let mut lane_active = Vec::new();
+        for (k, ray) in rays.iter().enumerate() {
+            match self.bvh.traverse(ray, t_max, &mut lane_active) {
+                Some((start, end)) => {
+                    t[k] = start.max(iso_value);
+                    t_end[k] = end;
+                    active.extend_from_slice(&lane_active);
                 }
-            });
+                None => done[k] = true,
+            }
+        }
+        active.sort_unstable();
+        active.dedup();
+
+        for _ in 0..self.settings.max_steps {
+            if done.iter().all(|d| *d) {
+                return marches;
+            }
+
+            let p = Vec3Packet::from_fn(|k| rays[k].at(t[k]));
+            let d = match self.settings.precision {
+                Precision::Double => self.distance_packet(&p, &active),
+                Precision::Single => lanes(|k| self.march_distance(p.get(k), &active)),
+            };
 
-        let _stop = self.get_time();
-        println!("render time {:?}", _stop - _start);
+            for (k, march) in marches.iter_mut().enumerate() {
+                if done[k] {
+                    continue;
+                }
+                march.iterations += 1;
+                march.distance = d[k];
+
+                if d[k] < self.hit_threshold(&rays[k], t[k]) {
+                    march.t = t[k] + d[k] * step_size;
+                    march.termination = Termination::Hit;
+                    done[k] = true;
+                    continue;
+                }
+
+                t[k] += d[k] * step_size;
+
+                if t[k] > t_end[k] {
+                    march.t = t[k];
+                    done[k] = true;
+                }
+            }
+        }
+
+        for (k, march) in marches.iter_mut().enumerate() {
+            if !done[k] {
+                march.t = t[k];
+                march.termination = Termination::StepLimit;
+            }
+        }
+        marches
     }
 
-    pub fn distance(&self, p: Vec3d) -> f64 {
-        let mut d = length(p - vec3d(0.0, 0.0, 0.0)) - 1.0;
+    /// Plain sphere tracing, returns the march and the interval between the last point outside
+    /// and the hit point.
+    fn march_naive(
+        &self,
+        ray: &Ray,
+        active: &[usize],
+        t_start: f64,
+        t_max: f64,
+    ) -> (March, (f64, f64)) {
+        let iso_value = self.settings.iso_value;
+        let step_size = self.settings.step_size;
+        let mut t = t_start.max(iso_value);
+        let mut t_outside = t;
+
+        let mut march = March {
+            termination: Termination::StepLimit,
+            t,
+            iterations: 0,
+            distance: f64::MAX,
+        };
+
+        for i in 0..self.settings.max_steps {
+            let p = ray.at(t);
+
+            let d = self.march_distance(p, active);
+
+            march.iterations = i + 1;
+            march.distance = d;
+
+            if d < self.hit_threshold(ray, t) {
+                march.t = t + d * step_size;
+                march.termination = Termination::Hit;
+                return (march, (t_outside, t));
+            }
+
+            t_outside = t;
+            t += d * step_size;
+
+            if t > t_max {
+                march.termination = Termination::Escaped;
+                break;
+            }
+        }
+
+        march.t = t;
+        (march, (t_outside, t))
+    }
+
+    /// Over-relaxed sphere tracing, returns the march and the interval between the last point
+    /// outside and the hit point.
+    fn march_relaxed(
+        &self,
+        ray: &Ray,
+        active: &[usize],
+        t_start: f64,
+        t_max: f64,
+    ) -> (March, (f64, f64)) {
+        let iso_value = self.settings.iso_value;
+        let step_size = self.settings.step_size;
+        let mut omega = self.settings.relaxation.clamp(1.0, 1.99);
+
+        let mut t = t_start.max(iso_value);
+        let mut t_prev = t;
+        let mut prev_radius = 0.0;
+        let mut step = 0.0;
+
+        let mut march = March {
+            termination: Termination::StepLimit,
+            t,
+            iterations: 0,
+            distance: f64::MAX,
+        };
+
+        for i in 0..self.settings.max_steps {
+            let p = ray.at(t);
+
+            let d = self.march_distance(p, active);
+
+            march.iterations = i + 1;
+
+            // If the spheres of the last two points do not overlap the relaxed step may have
+            // skipped a surface, go back and continue with plain steps.
+            if omega > 1.0 && d.abs() + prev_radius < step {
+                omega = 1.0;
+                step = prev_radius * step_size;
+                t = t_prev + step;
+                continue;
+            }
+
+            march.distance = d;
+
+            if d < self.hit_threshold(ray, t) {
+                march.t = t + d * step_size;
+                march.termination = Termination::Hit;
+                return (march, (t_prev, t));
+            }
+
+            t_prev = t;
+            prev_radius = d;
+            step = d * omega * step_size;
+            t += step;
+
+            if t > t_max {
+                march.termination = Termination::Escaped;
+                break;
+            }
+        }
+
+        march.t = t;
+        (march, (t_prev, t))
+    }
+
+    /// Bisects the interval of a hit which overshot the surface to place it on the surface.
+    fn refine(&self, ray: &Ray, active: &[usize], march: &mut March, (mut a, mut b): (f64, f64)) {
+        let mut d = march.distance;
+
+        for _ in 0..10 {
+            let t = (a + b) * 0.5;
+            d = self.march_distance(ray.at(t), active);
+            march.iterations += 1;
+
+            if d < 0.0 {
+                b = t;
+            } else {
+                a = t;
+            }
+
+            if d.abs() < self.hit_threshold(ray, t) {
+                break;
+            }
+        }
+
+        march.t = (a + b) * 0.5;
+        march.distance = d;
+    }
+
+    /// Returns the range of the scene distance over the box, a positive range proves the box
+    /// empty and a negative range proves it full.
+    pub fn map_interval(&self, bounds: &Aabb) -> Interval {
+        let mut d: Option<Interval> = None;
+
+        for object in &self.objects {
+            d = object
+                .op
+                .apply_interval(d, object.distance_interval(bounds), object.smoothing);
+        }
+
+        d.unwrap_or(Interval::point(f64::MAX)) + self.displacement_interval(bounds)
+    }
+
+    /// Returns the range of the displacement expression over the box.
+    fn displacement_interval(&self, bounds: &Aabb) -> Interval {
+        if self.expr.is_none() {
+            return Interval::point(0.0);
+        }
+        match &self.displacement {
+            Some(expr) => expr.eval(&Interval3::from_aabb(bounds)),
+            None => Interval::entire(),
+        }
+    }
+
+    /// Computes tight bounds of the displaced object by subdividing its primitive bounds and
+    /// dropping the cells proven empty.
+    fn object_bounds(&self, object: &Object) -> Aabb {
+        // The largest distance the displacement can push the surface out of the primitive
+        let everywhere = Aabb::new(
+            vec3d(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
+            vec3d(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+        );
+        let reach = (-self.displacement_interval(&everywhere).lo).max(0.0);
+        let primitive = object.bounds();
+        if !reach.is_finite() {
+            return everywhere;
+        }
+
+        let mut bounds = Aabb::empty();
+        self.tighten_bounds(object, primitive.expand(reach), 3, &mut bounds);
+        bounds
+    }
+
+    fn tighten_bounds(&self, object: &Object, cell: Aabb, depth: usize, bounds: &mut Aabb) {
+        let d = object.distance_interval(&cell) + self.displacement_interval(&cell);
+
+        // Smooth operations reach into the neighbourhood of the surface
+        if d.lo > object.smoothing.max(0.0) {
+            return;
+        }
+        if depth == 0 || d.is_negative() {
+            *bounds = bounds.union(&cell);
+            return;
+        }
+
+        let c = cell.center();
+        for i in 0..8 {
+            let (min_x, max_x) = if i & 1 == 0 {
+                (cell.min.x, c.x)
+            } else {
+                (c.x, cell.max.x)
+            };
+            let (min_y, max_y) = if i & 2 == 0 {
+                (cell.min.y, c.y)
+            } else {
+                (c.y, cell.max.y)
+            };
+            let (min_z, max_z) = if i & 4 == 0 {
+                (cell.min.z, c.z)
+            } else {
+                (c.z, cell.max.z)
+            };
+            let octant = Aabb::new(vec3d(min_x, min_y, min_z), vec3d(max_x, max_y, max_z));
+            self.tighten_bounds(object, octant, depth - 1, bounds);
+        }
+    }
+
+    /// Tests if the scene is proven empty inside the frustum of the pixel rectangle
+    /// (x0, y0, x1, y1) up to t_max. The frustum is covered by boxes around slices of constant
+    /// depth along the view direction.
+    fn tile_is_empty(
+        &self,
+        camera: &Camera,
+        mode: CameraMode,
+        forward: Vec3d,
+        (x0, y0, x1, y1): (usize, usize, usize, usize),
+        screen: Vec2d,
+    ) -> bool {
+        const SLICES: usize = 16;
+
+        let corners = [
+            (x0 as f64 - 0.5, y0 as f64 - 0.5),
+            (x1 as f64 - 0.5, y0 as f64 - 0.5),
+            (x0 as f64 - 0.5, y1 as f64 - 0.5),
+            (x1 as f64 - 0.5, y1 as f64 - 0.5),
+        ]
+        .map(|(x, y)| {
+            camera.create_camera_ray(
+                mode,
+                vec2d(x / screen.x, y / screen.y),
+                screen,
+                vec2d(0.0, 0.0),
+            )
+        });
+
+        if corners.iter().any(|ray| dot(ray.d, forward) <= 0.0) {
+            return false;
+        }
+
+        let t_max = self.settings.t_max;
+        for s in 0..SLICES {
+            let mut slice = Aabb::empty();
+            let mut threshold: f64 = 0.0;
+            for z in [s as f64 / SLICES as f64, (s + 1) as f64 / SLICES as f64] {
+                for ray in &corners {
+                    let t = z * t_max / dot(ray.d, forward);
+                    slice.extend(ray.at(t));
+                    threshold = threshold.max(self.hit_threshold(ray, t));
+                }
+            }
+            if self.map_interval(&slice).lo <= threshold {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// Evaluates the scene at p, combining the objects in order.
+    pub fn map(&self, p: Vec3d) -> SdfHit {
+        let mut hit = SdfHit::miss();
+
+        for (index, object) in self.objects.iter().enumerate() {
+            let d = self.object_distance(index, p);
+            hit = object
+                .op
+                .apply(hit, SdfHit::new(d, index), object.smoothing);
+        }
+
+        self.displace(p, hit)
+    }
+
+    /// Evaluates the scene at p for a ray which only crosses the bounds of the sorted active
+    /// objects. Skipped unions and subtractions do not change the surface along the ray, a
+    /// skipped intersection removes everything combined before it.
+    pub fn map_active(&self, p: Vec3d, active: &[usize]) -> SdfHit {
+        let mut hit = SdfHit::miss();
+        let mut active = active.iter().peekable();
+
+        for (index, object) in self.objects.iter().enumerate() {
+            if active.next_if_eq(&&index).is_some() {
+                let d = self.object_distance(index, p);
+                hit = object
+                    .op
+                    .apply(hit, SdfHit::new(d, index), object.smoothing);
+            } else if object.op == CsgOp::Intersection {
+                hit = SdfHit::miss();
+            }
+        }
+
+        self.displace(p, hit)
+    }
+
+    /// Evaluates the scene distance of the packet, only the sorted active objects are evaluated
+    /// like in [`Tracer::map_active`].
+    pub fn distance_packet(&self, p: &Vec3Packet, active: &[usize]) -> F64x {
+        let mut d = F64x::splat(f64::MAX);
+        let mut active = active.iter().peekable();
+
+        for (index, object) in self.objects.iter().enumerate() {
+            if active.next_if_eq(&&index).is_some() {
+                let b = match self.caches.get(index) {
+                    Some(Some(_)) => lanes(|k| self.object_distance(index, p.get(k))),
+                    _ => object.distance_packet(p),
+                };
+                for (d, b) in d.0.iter_mut().zip(b.0) {
+                    *d = object.op.apply_distance(*d, b, object.smoothing);
+                }
+            } else if object.op == CsgOp::Intersection {
+                d = F64x::splat(f64::MAX);
+            }
+        }
+
+        if let Some(expr) = &self.expr {
+            for (k, d) in d.0.iter_mut().enumerate() {
+                if let Ok(v) = expr.eval(&[p.x[k]]) {
+                    *d += v;
+                }
+            }
+        }
+
+        d
+    }
+
+    /// The scene distance used while marching, evaluated in the precision of the settings.
+    /// Shading always evaluates the scene in double precision.
+    #[inline(always)]
+    pub fn march_distance(&self, p: Vec3d, active: &[usize]) -> f64 {
+        match self.settings.precision {
+            Precision::Double => self.map_active(p, active).distance,
+            Precision::Single => {
+                let p = vec3f(p.x as f32, p.y as f32, p.z as f32);
+                self.single.distance(p, active) as f64
+            }
+        }
+    }
+
+    /// The distance of the object with the given index, from its cache where possible.
+    #[inline(always)]
+    fn object_distance(&self, index: usize, p: Vec3d) -> f64 {
+        if let Some(Some(cache)) = self.caches.get(index) {
+            if let Some(d) = cache.distance(p) {
+                return d;
+            }
+        }
+        self.objects[index].distance(p)
+    }
+
+    /// Adds the displacement expression to the distance of the hit.
+    fn displace(&self, p: Vec3d, mut hit: SdfHit) -> SdfHit {
         // d += clamp(sin(p.x * 20.0 - 1.0) * 0.1, 0.0, 1.0);
         if let Some(expr) = &self.expr {
             if let Ok(v) = expr.eval(&[p.x]) {
-                d += v;
+                hit.distance += v;
             }
         }
 
-        d
+        hit
     }
 
-    pub fn normal(&self, p: Vec3d) -> Vec3d {
-        let scale = 0.5773 * 0.0005;
+    pub fn distance(&self, p: Vec3d) -> f64 {
+        self.map(p).distance
+    }
+
+    /// Returns the material of the hit, blending the materials of smooth operations.
+    pub fn material(&self, hit: &SdfHit) -> Material {
+        if let Some(object) = self.objects.get(hit.id) {
+            if hit.blend > 0.0 {
+                if let Some(other) = self.objects.get(hit.blend_id) {
+                    return object.material.mix(&other.material, hit.blend);
+                }
+            }
+            object.material.clone()
+        } else {
+            Material::default()
+        }
+    }
+
+    /// Evaluates the scene distance and its gradient at p in a single pass. Returns None if the
+    /// displacement expression has no dual evaluation.
+    pub fn map_dual(&self, p: Vec3d) -> Option<Dual> {
+        let q = Dual3::variable(p);
+        let mut d = Dual::empty();
+
+        for object in &self.objects {
+            d = object
+                .op
+                .apply_distance(d, object.eval(&q), object.smoothing);
+        }
+
+        match (&self.expr, &self.displacement) {
+            (Some(_), Some(expr)) => Some(d + expr.eval(&q)),
+            (Some(_), None) => None,
+            (None, _) => Some(d),
+        }
+    }
+
+    /// The surface normal at p, the normalized analytic gradient of the distance field. Where
+    /// the gradient is not available it is estimated with finite differences of the given
+    /// step, usually the hit threshold of the ray.
+    pub fn normal(&self, p: Vec3d, step: f64) -> Vec3d {
+        match self.map_dual(p) {
+            Some(d) if length(d.d) > 1e-12 => normalize(d.d),
+            _ => self.normal_numeric(p, step),
+        }
+    }
+
+    /// Returns how sharp the surface around p is, the largest angle in radians between the
+    /// normal at p and the normals of the surface points at the given radius around it. Edges
+    /// of boxes and of hard CSG operations give large angles, smooth surfaces angles near zero.
+    pub fn feature_angle(&self, p: Vec3d, radius: f64) -> f64 {
+        let step = radius * 0.1;
+        let n = self.normal(p, step);
+        let axis = if n.x.abs() < 0.9 {
+            vec3d(1.0, 0.0, 0.0)
+        } else {
+            vec3d(0.0, 1.0, 0.0)
+        };
+        let u = normalize(cross(n, axis));
+        let v = cross(n, u);
+
+        let mut min_cos: f64 = 1.0;
+        for offset in [u, Vec3d::zero() - u, v, Vec3d::zero() - v] {
+            let mut q = p + offset * radius;
+
+            // A Newton step moves the sample back onto the surface
+            if let Some(d) = self.map_dual(q) {
+                let g = dot(d.d, d.d);
+                if g > 1e-12 {
+                    q = q - d.d * (d.v / g);
+                }
+            }
+            min_cos = min_cos.min(dot(n, self.normal(q, step)));
+        }
+
+        min_cos.clamp(-1.0, 1.0).acos()
+    }
+
+    /// The normal from finite differences, used when the gradient is not available.
+    fn normal_numeric(&self, p: Vec3d, step: f64) -> Vec3d {
+        let scale = 0.5773 * step;
         let e = vec2d(1.0 * scale, -1.0 * scale);
 
         // IQs normal function
@@ -155,21 +1344,257 @@ impl Tracer {
         normalize(n)
     }
 
-    /// Gets the current time in milliseconds
-    fn get_time(&self) -> u128 {
-        let time;
-        #[cfg(not(target_arch = "wasm32"))]
-        {
-            use std::time::{SystemTime, UNIX_EPOCH};
-            let t = SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .expect("Time went backwards");
-            time = t.as_millis();
+    /// Shades the hit of the ray at t, reflection and refraction rays are traced until depth
+    /// reaches the max depth of the settings.
+    fn shade_hit(&self, ray: &Ray, t: f64, depth: usize) -> Vec3d {
+        let p = ray.at(t);
+        let step = self.hit_threshold(ray, t);
+        let normal = self.normal(p, step);
+        let material = self.material(&self.map(p));
+
+        // Secondary rays start just off the surface
+        let bias = self.surface_bias(step);
+        let trace = depth < self.settings.max_depth;
+
+        let glossy = material.roughness < GLOSSY_ROUGHNESS || material.transmission > 0.0;
+        let reflection = if trace && glossy {
+            let r = reflect_dir(ray.d, normal);
+            Some(self.trace_radiance(&Ray::new(p + normal * bias, r), depth + 1))
+        } else {
+            None
+        };
+
+        let c = self.shade(p, normal, ray.d, &material, bias, reflection);
+        if material.transmission <= 0.0 {
+            return c;
         }
-        #[cfg(target_arch = "wasm32")]
-        {
-            time = web_sys::window().unwrap().performance().unwrap().now() as u128;
+
+        // The transmissive part is a dielectric which reflects by the Fresnel term of its ior,
+        // the refracted ray marches through the solid and leaves it on the other side. At the
+        // max depth both directions fall back to the environment
+        let cos_i = dot(normal, -ray.d).max(0.0);
+        let f0 = ((material.ior - 1.0) / (material.ior + 1.0)).powi(2);
+        let (fresnel, transmitted) = match refract_dir(ray.d, normal, 1.0 / material.ior) {
+            Some(dir) => {
+                let fresnel = fresnel_schlick(vec3d(f0, f0, f0), cos_i).x;
+                let transmitted = if trace {
+                    let inside = Ray::new(p - normal * bias, dir);
+                    self.trace_interior(&inside, material.ior, depth + 1)
+                } else {
+                    self.background(dir)
+                };
+                (fresnel, transmitted * material.color)
+            }
+            None => (1.0, Vec3d::zero()),
+        };
+        let reflected = reflection.unwrap_or_else(|| self.background(reflect_dir(ray.d, normal)));
+        let dielectric = reflected * fresnel + transmitted * (1.0 - fresnel);
+
+        c * (1.0 - material.transmission) + dielectric * material.transmission
+    }
+
+    /// Traces a secondary ray and returns the radiance arriving along it.
+    fn trace_radiance(&self, ray: &Ray, depth: usize) -> Vec3d {
+        let march = self.march(ray, self.settings.t_max);
+        if march.hit() {
+            self.shade_hit(ray, march.t, depth)
+        } else {
+            self.background(ray.d)
+        }
+    }
+
+    /// Marches a refracted ray through the inside of a solid, where the negated distance is the
+    /// distance to the surface, and returns the radiance leaving the solid along the ray. Rays
+    /// which are totally reflected at the surface stay inside.
+    fn trace_interior(&self, ray: &Ray, ior: f64, depth: usize) -> Vec3d {
+        let Some((p, normal)) = self.exit(ray) else {
+            return Vec3d::zero();
+        };
+
+        let bias = self.surface_bias(self.settings.iso_value);
+        match refract_dir(ray.d, Vec3d::zero() - normal, ior) {
+            Some(dir) => self.trace_radiance(&Ray::new(p + normal * bias, dir), depth),
+            None if depth < self.settings.max_depth => {
+                let r = reflect_dir(ray.d, normal);
+                self.trace_interior(&Ray::new(p - normal * bias, r), ior, depth + 1)
+            }
+            None => Vec3d::zero(),
         }
-        time
     }
+
+    /// Marches a ray through the inside of a solid and returns the point where it leaves the
+    /// solid together with the outward normal there.
+    pub fn exit(&self, ray: &Ray) -> Option<(Vec3d, Vec3d)> {
+        let iso_value = self.settings.iso_value;
+        let mut t = 0.0;
+
+        for _ in 0..self.settings.max_steps {
+            let p = ray.at(t);
+            let d = -self.distance(p);
+
+            if d < iso_value {
+                return Some((p, self.normal(p, iso_value)));
+            }
+
+            t += d * self.settings.step_size;
+            if t > self.settings.t_max {
+                break;
+            }
+        }
+
+        None
+    }
+
+    /// Direct lighting of the hit point p with the given normal, viewed along rd. Shadow rays and
+    /// occlusion samples start at the bias off the surface. The reflection is the radiance
+    /// traced in the mirror direction, the environment is used without it.
+    pub fn shade(
+        &self,
+        p: Vec3d,
+        normal: Vec3d,
+        rd: Vec3d,
+        material: &Material,
+        bias: f64,
+        reflection: Option<Vec3d>,
+    ) -> Vec3d {
+        let (f0, albedo) = specular_diffuse(material);
+
+        // Normalized Blinn-Phong lobe approximating the roughness
+        let alpha = (material.roughness * material.roughness).max(0.001);
+        let power = (2.0 / (alpha * alpha) - 2.0).max(1.0);
+        let spec_norm = (power + 8.0) / (8.0 * std::f64::consts::PI);
+
+        let ao = self.ambient_occlusion(p, normal, bias);
+        let mut c = material.emission;
+
+        let r = reflect_dir(rd, normal);
+        let f = fresnel_schlick(f0, dot(normal, -rd).max(0.0));
+        if let Some(irradiance) = self.irradiance(normal) {
+            // Diffuse irradiance and a roughness weighted blend for glossy reflections
+            let mirror = reflection.unwrap_or_else(|| self.background(r));
+            let reflection = mirror * (1.0 - material.roughness)
+                + self.irradiance(r).unwrap_or(irradiance) * material.roughness;
+            c += (albedo * irradiance + f * reflection) * ao;
+        } else {
+            c += material.color * (0.05 * ao);
+            if let Some(mirror) = reflection {
+                c += f * mirror * (1.0 - material.roughness);
+            }
+        }
+
+        for light in &self.lights {
+            let (l, distance) = light.direction_from(p);
+            let diffuse = dot(normal, l).max(0.0);
+            if diffuse <= 0.0 {
+                continue;
+            }
+
+            let h = normalize(l - rd);
+            let specular = spec_norm * dot(normal, h).max(0.0).powf(power);
+
+            let mut shadow = 1.0;
+            if self.settings.shadows {
+                let t_max = distance.min(self.settings.t_max);
+                shadow = self.soft_shadow(p + normal * bias, l, bias, t_max, light.shadow_k);
+            }
+
+            c += (albedo + f0 * specular) * light.radiance(distance) * (diffuse * shadow);
+        }
+
+        c
+    }
+
+    /// Ambient occlusion by sampling the distance field along the normal, returns 1.0 for
+    /// unoccluded points. The samples are spaced relative to the surface bias, a bias of 0.01
+    /// samples from 0.01 to 0.13.
+    pub fn ambient_occlusion(&self, p: Vec3d, normal: Vec3d, bias: f64) -> f64 {
+        let ao_steps = self.settings.ao_steps;
+        if ao_steps == 0 {
+            return 1.0;
+        }
+
+        let mut occ = 0.0;
+        let mut sca = 1.0;
+
+        let scale = bias / 0.01;
+        let steps_f = (ao_steps.max(2) - 1) as f64;
+        for i in 0..ao_steps {
+            let h = (0.01 + 0.12 * i as f64 / steps_f) * scale;
+            let d = self.distance(p + normal * h);
+            occ += (h - d) / scale * sca;
+            sca *= 0.95;
+        }
+
+        (1.0 - self.settings.ao_strength * occ).clamp(0.0, 1.0)
+    }
+
+    /// Sphere traced soft shadow towards l, returns the visibility in [0, 1]. The march starts
+    /// and steps at least by the surface bias. Based on
+    /// https://iquilezles.org/articles/rmshadows/
+    pub fn soft_shadow(&self, ro: Vec3d, l: Vec3d, bias: f64, t_max: f64, k: f64) -> f64 {
+        let ray = Ray::new(ro, l);
+
+        let mut res: f64 = 1.0;
+        let mut t = bias;
+
+        for _ in 0..64 {
+            let h = self.distance(ray.at(t));
+            res = res.min(k * h / t);
+            t += h.clamp(bias * 0.5, 0.5);
+
+            if res < -1.0 || t > t_max {
+                break;
+            }
+        }
+
+        res = res.max(-1.0);
+        0.25 * (1.0 + res) * (1.0 + res) * (2.0 - res)
+    }
+}
+
+/// Maps v in [0, 1] to a blue, green, yellow, red heatmap.
+pub fn heatmap(v: f64) -> Vec3d {
+    let v = v.clamp(0.0, 1.0);
+    let r = (1.5 - (4.0 * v - 3.0).abs()).clamp(0.0, 1.0);
+    let g = (1.5 - (4.0 * v - 2.0).abs()).clamp(0.0, 1.0);
+    let b = (1.5 - (4.0 * v - 1.0).abs()).clamp(0.0, 1.0);
+    vec3d(r, g, b)
+}
+
+/// The color of a ray in the march debug view. Hits show the iteration heatmap and turn red
+/// when the ray overshot the surface, escaped rays are gray and rays which ran out of steps
+/// are magenta, brighter for larger remaining distances.
+pub fn march_debug_color(march: &March, settings: &RenderSettings) -> Vec3d {
+    let iso_value = settings.iso_value;
+    let steps = march.iterations as f64 / settings.max_steps as f64;
+
+    match march.termination {
+        Termination::Hit => {
+            let c = heatmap(steps) * 0.7;
+            let overshoot = (-march.distance / iso_value).max(1.0).log10() / 4.0;
+            let o = overshoot.clamp(0.0, 1.0);
+            c * (1.0 - o) + vec3d(1.0, 0.0, 0.0) * o
+        }
+        Termination::Escaped => {
+            let g = 0.1 + 0.3 * steps;
+            vec3d(g, g, g)
+        }
+        Termination::StepLimit => {
+            let error = (march.distance.abs() / iso_value).max(1.0).log10() / 4.0;
+            vec3d(1.0, 0.0, 1.0) * (0.5 + 0.5 * error.clamp(0.0, 1.0))
+        }
+    }
+}
+
+/// A stable false color for the object with the given index.
+pub fn id_color(id: usize) -> Vec3d {
+    if id == usize::MAX {
+        return Vec3d::zero();
+    }
+    let mut rng = Pcg32::new(id as u64);
+    vec3d(
+        0.2 + 0.8 * rng.next_f64(),
+        0.2 + 0.8 * rng.next_f64(),
+        0.2 + 0.8 * rng.next_f64(),
+    )
 }
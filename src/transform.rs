@@ -0,0 +1,69 @@
+use crate::prelude::*;
+
+/// The placement of an object, the rotation is given in degrees and applied in x, y, z order.
+/// The scale is uniform to keep the distance field exact.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct Transform {
+    pub position: Vec3d,
+    pub rotation: Vec3d,
+    pub scale: f64,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Transform {
+    pub fn new() -> Self {
+        Self {
+            position: Vec3d::zero(),
+            rotation: Vec3d::zero(),
+            scale: 1.0,
+        }
+    }
+
+    /// Transforms the world space point p into local space.
+    pub fn to_local(&self, p: Vec3d) -> Vec3d {
+        let q = self.to_local_scalar(&Scalar3::constant(p));
+        vec3d(q.x, q.y, q.z)
+    }
+
+    /// Transforms the world space point p of any scalar type into local space.
+    #[inline(always)]
+    pub fn to_local_scalar<T: Scalar>(&self, p: &Scalar3<T>) -> Scalar3<T> {
+        let mut q = p.translate(Vec3d::zero() - self.position);
+        q = q.rotate_z(-self.rotation.z.to_radians());
+        q = q.rotate_y(-self.rotation.y.to_radians());
+        q = q.rotate_x(-self.rotation.x.to_radians());
+        q.scale(1.0 / self.scale)
+    }
+
+    /// Transforms the local space point p into world space.
+    pub fn to_world(&self, p: Vec3d) -> Vec3d {
+        let mut q = p * self.scale;
+        q = rotate_x(q, self.rotation.x.to_radians());
+        q = rotate_y(q, self.rotation.y.to_radians());
+        q = rotate_z(q, self.rotation.z.to_radians());
+        q + self.position
+    }
+}
+
+/// Rotates p around the x axis by the angle in radians.
+pub fn rotate_x(p: Vec3d, angle: f64) -> Vec3d {
+    let (s, c) = angle.sin_cos();
+    vec3d(p.x, c * p.y - s * p.z, s * p.y + c * p.z)
+}
+
+/// Rotates p around the y axis by the angle in radians.
+pub fn rotate_y(p: Vec3d, angle: f64) -> Vec3d {
+    let (s, c) = angle.sin_cos();
+    vec3d(c * p.x + s * p.z, p.y, -s * p.x + c * p.z)
+}
+
+/// Rotates p around the z axis by the angle in radians.
+pub fn rotate_z(p: Vec3d, angle: f64) -> Vec3d {
+    let (s, c) = angle.sin_cos();
+    vec3d(c * p.x - s * p.y, s * p.x + c * p.y, p.z)
+}
@@ -0,0 +1,200 @@
+use crate::prelude::*;
+use rayon::prelude::*;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+
+/// A frame to render in the background.
+#[derive(Clone, Debug)]
+pub struct RenderJob {
+    pub project: Project,
+    pub pass: RenderPass,
+    /// Progressively path trace the frame instead of rendering it once in tiles.
+    pub path_tracing: bool,
+    pub width: usize,
+    pub height: usize,
+}
+
+pub enum RenderMessage {
+    /// The worker started rendering the job in the given number of steps, the tiles of the
+    /// frame or the samples per pixel of path traced jobs.
+    Started { generation: usize, steps: usize },
+    /// A finished tile, x and y are the top left corner in the buffer of the job.
+    Tile {
+        generation: usize,
+        x: usize,
+        y: usize,
+        buffer: TheRGBABuffer,
+    },
+    /// All tiles or samples of the job were sent.
+    Finished { generation: usize },
+    /// The current estimate of a path traced job after the given number of samples per pixel.
+    Frame {
+        generation: usize,
+        samples: u32,
+        buffer: TheRGBABuffer,
+    },
+}
+
+/// Renders jobs on a background thread. The tiles of a job are rendered in parallel from the
+/// center out and sent back as they finish, path traced jobs send the whole frame after every
+/// sample until the sample limit of the project settings. Every submitted job gets a new generation, the worker stops
+/// rendering a job as soon as the generation changes.
+pub struct RenderWorker {
+    jobs: Sender<(usize, RenderJob)>,
+    messages: Receiver<RenderMessage>,
+    generation: Arc<AtomicUsize>,
+}
+
+impl Default for RenderWorker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RenderWorker {
+    pub fn new() -> Self {
+        let (jobs, job_receiver) = channel::<(usize, RenderJob)>();
+        let (message_sender, messages) = channel();
+        let generation = Arc::new(AtomicUsize::new(0));
+
+        let current = generation.clone();
+        thread::spawn(move || {
+            let mut tracer = Tracer::new();
+            let mut path_tracer = PathTracer::new();
+
+            while let Ok(mut job) = job_receiver.recv() {
+                // Skip to the latest job
+                while let Ok(newer) = job_receiver.try_recv() {
+                    job = newer;
+                }
+                let (job_generation, job) = job;
+
+                if current.load(Ordering::Relaxed) != job_generation {
+                    continue;
+                }
+
+                if job.path_tracing {
+                    let samples = job.project.settings.max_samples.max(1);
+                    let started = RenderMessage::Started {
+                        generation: job_generation,
+                        steps: samples as usize,
+                    };
+                    if message_sender.send(started).is_err() {
+                        return;
+                    }
+
+                    let mut buffer =
+                        TheRGBABuffer::new(TheDim::new(0, 0, job.width as i32, job.height as i32));
+                    tracer.prepare(&job.project);
+                    path_tracer.reset();
+                    while path_tracer.samples() < samples {
+                        if current.load(Ordering::Relaxed) != job_generation {
+                            break;
+                        }
+                        path_tracer.render(&mut buffer, &job.project, &tracer);
+                        let message = RenderMessage::Frame {
+                            generation: job_generation,
+                            samples: path_tracer.samples(),
+                            buffer: buffer.clone(),
+                        };
+                        if message_sender.send(message).is_err() {
+                            return;
+                        }
+                    }
+
+                    if path_tracer.samples() >= samples {
+                        let message = RenderMessage::Finished {
+                            generation: job_generation,
+                        };
+                        if message_sender.send(message).is_err() {
+                            return;
+                        }
+                    }
+                    continue;
+                }
+
+                tracer.prepare(&job.project);
+
+                let tiles = center_out_tiles(job.width, job.height, RENDER_TILE_SIZE);
+                let started = RenderMessage::Started {
+                    generation: job_generation,
+                    steps: tiles.len(),
+                };
+                if message_sender.send(started).is_err() {
+                    return;
+                }
+
+                // Every thread takes the next tile in order, keeping the center-out schedule
+                let next = AtomicUsize::new(0);
+                let cancelled = AtomicBool::new(false);
+                let tracer = &tracer;
+                let job = &job;
+                (0..rayon::current_num_threads())
+                    .into_par_iter()
+                    .for_each_with(message_sender.clone(), |sender, _| loop {
+                        if current.load(Ordering::Relaxed) != job_generation {
+                            cancelled.store(true, Ordering::Relaxed);
+                            break;
+                        }
+                        let Some(tile) = tiles.get(next.fetch_add(1, Ordering::Relaxed)) else {
+                            break;
+                        };
+
+                        let mut buffer = tile.buffer();
+                        tracer.render_region(
+                            &mut buffer,
+                            &job.project,
+                            job.pass,
+                            (tile.x, tile.y),
+                            (job.width, job.height),
+                        );
+
+                        let message = RenderMessage::Tile {
+                            generation: job_generation,
+                            x: tile.x,
+                            y: tile.buffer_y(job.height),
+                            buffer,
+                        };
+                        if sender.send(message).is_err() {
+                            cancelled.store(true, Ordering::Relaxed);
+                            break;
+                        }
+                    });
+
+                if !cancelled.load(Ordering::Relaxed) {
+                    let message = RenderMessage::Finished {
+                        generation: job_generation,
+                    };
+                    if message_sender.send(message).is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+
+        Self {
+            jobs,
+            messages,
+            generation,
+        }
+    }
+
+    /// Cancels the current job and starts rendering the given one, returns its generation.
+    pub fn submit(&self, job: RenderJob) -> usize {
+        let generation = self.generation.fetch_add(1, Ordering::Relaxed) + 1;
+        let _ = self.jobs.send((generation, job));
+        generation
+    }
+
+    /// The generation of the latest job, messages of older generations are stale.
+    pub fn generation(&self) -> usize {
+        self.generation.load(Ordering::Relaxed)
+    }
+
+    /// Returns the next message of the worker without blocking.
+    pub fn try_recv(&self) -> Option<RenderMessage> {
+        self.messages.try_recv().ok()
+    }
+}